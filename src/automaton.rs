@@ -0,0 +1,41 @@
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug)]
+pub struct Automaton {
+    initial: usize,
+    finals: HashSet<usize>,
+    transitions: HashMap<(usize, i32), usize>,
+}
+
+impl Automaton {
+    pub fn new(initial: usize, finals: HashSet<usize>, transitions: HashMap<(usize, i32), usize>) -> Self {
+        Automaton {
+            initial,
+            finals,
+            transitions,
+        }
+    }
+
+    pub fn initial(&self) -> usize {
+        self.initial
+    }
+
+    pub fn is_final(&self, state: usize) -> bool {
+        self.finals.contains(&state)
+    }
+
+    pub fn next(&self, state: usize, value: i32) -> Option<usize> {
+        self.transitions.get(&(state, value)).cloned()
+    }
+
+    pub fn accepts(&self, word: &[i32]) -> bool {
+        let mut state = self.initial;
+        for &value in word {
+            match self.next(state, value) {
+                Some(next) => state = next,
+                None => return false,
+            }
+        }
+        self.is_final(state)
+    }
+}
@@ -1,10 +1,14 @@
 use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::iter::FromIterator;
 
-use crate::domain::Domain;
-use crate::variable::Variable;
+use crate::automaton::Automaton;
+use crate::domain::Domains;
+use crate::propagation::{Event, Priority};
+use crate::variable::{Assignments, VarId};
 use std::fmt::{self, Display, Formatter};
 use std::error::Error;
+use std::ops::Index;
 
 
 #[derive(Debug)]
@@ -29,13 +33,13 @@ impl Display for ConstraintError {
 impl Error for ConstraintError {}
 
 
-pub struct ConstraintVariable<'a, C: Constraint + PartialEq> {
-    pub constraint: &'a C,
-    pub variables: Vec<&'a Variable>,
+pub struct ConstraintVariable {
+    pub constraint: Box<dyn Constraint>,
+    pub variables: Vec<VarId>,
 }
 
-impl<'a, C: Constraint + PartialEq> ConstraintVariable<'a, C> {
-    pub fn new(constraint: &'a C, variables: &[&'a Variable]) -> Self {
+impl ConstraintVariable {
+    pub fn new(constraint: Box<dyn Constraint>, variables: &[VarId]) -> Self {
         ConstraintVariable {
             constraint,
             variables: Vec::from(variables),
@@ -43,36 +47,48 @@ impl<'a, C: Constraint + PartialEq> ConstraintVariable<'a, C> {
     }
 }
 
-struct UnassignedVariable<'a>(&'a Variable);
-static UNASSIGNED: UnassignedVariable = UnassignedVariable(&Variable::unassigned());
-
 
 pub trait Constraint {
-    fn call<'a>(&self,
-            variables: &[&'a Variable],
-            domains: &mut HashMap<&'a Variable, &mut Domain>,
-            assignments: &mut HashMap<&'a Variable, i32>,
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
             forward_check: bool) -> Result<bool, ConstraintError>;
 
 
-    fn pre_process<'a, C>(&self,
-                      variables: &[&'a Variable],
-                      domains: &mut HashMap<&'a Variable, &mut Domain>,
-                      constraints: &mut Vec<&ConstraintVariable<C>>,
-                      vconstraints: &mut HashMap<&'a Variable, Vec<&mut ConstraintVariable<C>>>)
-        where
-            C: Constraint + PartialEq + PartialEq<Self>,
+    /// Events on its variables waking the constraint up during propagation. Every variable must
+    /// be subscribed to, so that the constraint is checked once they are all assigned, unless
+    /// `resubscribe` keeps the constraint subscribed to whatever can still make it fail.
+    fn subscriptions(&self, variables: &[VarId]) -> Vec<(VarId, Event)> {
+        variables.iter().map(|&variable| (variable, Event::Removed)).collect()
+    }
+
+    /// Called by the solver before the search and after each call, with the subscriptions the
+    /// constraint has: new ones replacing them, or None to keep them. The subscriptions are not
+    /// restored on backtracking.
+    fn resubscribe(&self,
+                   _variables: &[VarId],
+                   _subscriptions: &[(VarId, Event)],
+                   _domains: &mut Domains,
+                   _assignments: &mut Assignments) -> Option<Vec<(VarId, Event)>> {
+        None
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Cheap
+    }
+
+    // returns true when the domains now enforce the constraint, which can then be dropped
+    fn pre_process(&self,
+                   variables: &[VarId],
+                   domains: &mut Domains) -> bool
     {
-        self.default_pre_process(variables, domains, constraints, vconstraints)
+        self.default_pre_process(variables, domains)
     }
 
-    fn default_pre_process<'a, C>(&self,
-                              variables: &[&'a Variable],
-                              domains: &mut HashMap<&'a Variable, &mut Domain>,
-                              constraints: &mut Vec<&ConstraintVariable<C>>,
-                              vconstraints: &mut HashMap<&'a Variable, Vec<&mut ConstraintVariable<C>>>)
-        where
-            C: Constraint + PartialEq + PartialEq<Self>,
+    fn default_pre_process(&self,
+                           variables: &[VarId],
+                           domains: &mut Domains) -> bool
     {
         if variables.len() == 1 {
             let variable = variables[0];
@@ -80,47 +96,43 @@ pub trait Constraint {
 
             // to satisfy the borrow checker
             {
-                let domain = domains.get(variable).unwrap();
-                domain_values = domain.values.clone();
+                let domain = domains.get(&variable).unwrap();
+                domain_values = domain.values();
             }
 
-            let mut to_removes: Vec<usize> = Vec::default();
+            let mut to_removes: Vec<i32> = Vec::default();
 
-            for (i, &value) in domain_values.iter().enumerate() {
-                let res = self.call(variables, domains, &mut HashMap::from_iter(vec![(variable, value)]), false);
+            for &value in domain_values.iter() {
+                let mut assignments = Assignments::new();
+                assignments.insert(variable, value);
+                let res = self.call(variables, domains, &mut assignments, false);
                 if let Ok(boolean) = res {
                     if !boolean {
-                        to_removes.push(i);
+                        to_removes.push(value);
                     }
                 }
             }
 
 
-            let domain = domains.get_mut(variable).unwrap();
-            for index in to_removes {
-                domain.values.remove(index);
-            }
-
-
-            constraints.remove(constraints.iter().position(|cv| cv.constraint == self && cv.variables == variables).unwrap());
+            let domain = domains.get_mut(&variable).unwrap();
+            domain.retain(|value| !to_removes.contains(&value));
 
-            let index_to_remove = vconstraints[variable].iter().position(|cv| cv.constraint == self && cv.variables == variables).unwrap();
-            vconstraints.get_mut(variable).unwrap().remove(index_to_remove);
+            return true;
         }
+        false
     }
 
-    fn forward_check<'a>(&self,
-                     variables: &[&'a Variable],
-                     domains: &mut HashMap<&'a Variable, &mut Domain>,
-                     assignments: &mut HashMap<&'a Variable, i32>,
-                     unassigned: &'a Variable) -> bool
+    fn forward_check(&self,
+                     variables: &[VarId],
+                     domains: &mut Domains,
+                     assignments: &mut Assignments) -> bool
     {
-        let mut unassigned_variable = unassigned;
+        let mut unassigned_variable: Option<VarId> = None;
         let mut flag = false;
-        for variable in variables {
-            if assignments.contains_key(variable) {
-                if unassigned_variable == unassigned {
-                    unassigned_variable = variable;
+        for &variable in variables {
+            if !assignments.contains_key(&variable) {
+                if unassigned_variable.is_none() {
+                    unassigned_variable = Some(variable);
                 } else {
                     flag = true;
                     break;
@@ -128,40 +140,35 @@ pub trait Constraint {
             }
         }
 
-        if !flag {
-            if unassigned_variable != unassigned {
-                // Remove from the unassigned variable domain's all
-                // values which break our variable's constraints.
-                let domain_values: Option<Vec<i32>>;
-                {
-                    let domain = domains.get_mut(unassigned_variable);
-                    domain_values = match domain {
-                        Some(dom) => Some(dom.values.clone()),
-                        None => None,
-                    }
-                }
+        if let (false, Some(unassigned_variable)) = (flag, unassigned_variable) {
+            // Remove from the unassigned variable domain's all
+            // values which break our variable's constraints.
+            let domain_values: Option<Vec<i32>>;
+            {
+                let domain = domains.get_mut(&unassigned_variable);
+                domain_values = domain.map(|dom| dom.values());
+            }
 
-                let mut values_to_hide: Vec<i32> = Vec::default();
-                if let Some(values) = domain_values {
-                    for value in values {
-                        assignments.insert(unassigned_variable,value);
-                        let res = self.call(variables, domains, assignments, false);
-                        if let Ok(boolean) = res {
-                            if !boolean{
-                                values_to_hide.push(value);
-                            }
+            let mut values_to_hide: Vec<i32> = Vec::default();
+            if let Some(values) = domain_values {
+                for value in values {
+                    assignments.insert(unassigned_variable,value);
+                    let res = self.call(variables, domains, assignments, false);
+                    if let Ok(boolean) = res {
+                        if !boolean{
+                            values_to_hide.push(value);
                         }
                     }
+                }
 
-                    let domain = domains.get_mut(unassigned_variable).unwrap();
-                    for value in values_to_hide {
-                        domain.hide_value(value);
-                    }
-
-                    assignments.remove(&unassigned_variable);
-                } else {
-                    return false;
+                let domain = domains.get_mut(&unassigned_variable).unwrap();
+                for value in values_to_hide {
+                    domain.hide_value(value);
                 }
+
+                assignments.remove(&unassigned_variable);
+            } else {
+                return false;
             }
         }
         true
@@ -194,10 +201,10 @@ impl<F> Constraint for FunctionConstraint<F>
     where
         F: Fn(Vec<i32>) -> Result<bool, ConstraintError>
 {
-    fn call<'a>(&self,
-            variables: &[&'a Variable],
-            domains: &mut HashMap<&'a Variable, &mut Domain>,
-            assignments: &mut HashMap<&'a Variable, i32>,
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
             forward_check: bool) -> Result<bool, ConstraintError> {
         let mut parms: Vec<i32> = Vec::default();
         let mut missing = 0;
@@ -209,14 +216,12 @@ impl<F> Constraint for FunctionConstraint<F>
             }
         }
 
-        let UnassignedVariable(unassigned_var) = UNASSIGNED;
-
         if missing != 0 {
             Ok((self.assigned || (self.function)(parms)?)
                 && (
                 !forward_check
                     || missing != 1
-                    || self.forward_check(variables, domains, assignments, unassigned_var)))
+                    || self.forward_check(variables, domains, assignments)))
         } else {
             (self.function)(parms)
         }
@@ -224,7 +229,76 @@ impl<F> Constraint for FunctionConstraint<F>
 }
 
 
+/// Read-only view over the current assignments handed to `NamedFunctionConstraint`. Indexing
+/// panics on a variable which is not bound, `get` is for the ones which may not be.
+pub struct Assignment<'a> {
+    assignments: &'a Assignments,
+}
+
+impl<'a> Assignment<'a> {
+    pub fn get(&self, variable: VarId) -> Option<i32> {
+        self.assignments.get(&variable).cloned()
+    }
+
+    pub fn is_bound(&self, variable: VarId) -> bool {
+        self.assignments.contains_key(&variable)
+    }
+}
+
+impl<'a> Index<VarId> for Assignment<'a> {
+    type Output = i32;
+
+    fn index(&self, variable: VarId) -> &i32 {
+        self.assignments.get(&variable).expect("Variable is not bound.")
+    }
+}
+
+
+/// Constraint checked by a function reading the variables it names. When `assigned` the function
+/// is only called once all of them are bound and may index them, otherwise it is called on
+/// partial assignments too and reads them with `get`.
 #[derive(Debug)]
+pub struct NamedFunctionConstraint<F>
+    where
+        F: Fn(&Assignment) -> Result<bool, ConstraintError>
+{
+    function: F,
+    assigned: bool,
+}
+
+impl<F> NamedFunctionConstraint<F>
+    where
+        F: Fn(&Assignment) -> Result<bool, ConstraintError>
+{
+    pub fn new(function: F, assigned: bool) -> Self {
+        NamedFunctionConstraint {
+            function,
+            assigned,
+        }
+    }
+}
+
+impl<F> Constraint for NamedFunctionConstraint<F>
+    where
+        F: Fn(&Assignment) -> Result<bool, ConstraintError>
+{
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError> {
+        let missing = variables.iter().filter(|&variable| !assignments.contains_key(variable)).count();
+        let satisfied = (self.assigned && missing != 0) || (self.function)(&Assignment { assignments })?;
+
+        Ok(satisfied
+            && (!forward_check
+                || missing != 1
+                || self.forward_check(variables, domains, assignments)))
+    }
+}
+
+
+#[derive(Debug, Default)]
 pub struct AllDifferentConstraint {}
 
 impl AllDifferentConstraint {
@@ -234,10 +308,10 @@ impl AllDifferentConstraint {
 }
 
 impl Constraint for AllDifferentConstraint {
-    fn call<'a>(&self,
-                variables: &[&'a Variable],
-                domains: &mut HashMap<&'a Variable, &mut Domain>,
-                assignments: &mut HashMap<&'a Variable, i32>,
+    fn call(&self,
+                variables: &[VarId],
+                domains: &mut Domains,
+                assignments: &mut Assignments,
                 forward_check: bool) -> Result<bool, ConstraintError>
     {
         let mut seen: HashMap<i32, bool> = HashMap::default();
@@ -255,9 +329,9 @@ impl Constraint for AllDifferentConstraint {
                 if !assignments.contains_key(variable) {
                     if let Some(domain) = domains.get_mut(variable) {
                         for &value in seen.keys() {
-                            if domain.values.contains(&value) {
+                            if domain.contains(value) {
                                 domain.hide_value(value);
-                                if domain.values.len() == 0 {
+                                if domain.is_empty() {
                                     return Ok(false);
                                 }
                             }
@@ -268,10 +342,15 @@ impl Constraint for AllDifferentConstraint {
         }
         Ok(true)
     }
+
+    // only the assigned values are filtered out of the other domains
+    fn subscriptions(&self, variables: &[VarId]) -> Vec<(VarId, Event)> {
+        variables.iter().map(|&variable| (variable, Event::Assigned)).collect()
+    }
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct AllEqualConstraint {}
 
 impl AllEqualConstraint {
@@ -281,15 +360,15 @@ impl AllEqualConstraint {
 }
 
 impl Constraint for AllEqualConstraint {
-    fn call<'a>(&self,
-            variables: &[&'a Variable],
-            domains: &mut HashMap<&'a Variable, &mut Domain>,
-            assignments: &mut HashMap<&'a Variable, i32>,
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
             forward_check: bool) -> Result<bool, ConstraintError>
     {
         let mut single_value = i32::MIN;
         for &variable in variables.iter() {
-            if let Some(&value) = assignments.get(variable) {
+            if let Some(&value) = assignments.get(&variable) {
                 if single_value == i32::MIN {
                     single_value = value;
                 } else if value != single_value {
@@ -302,13 +381,13 @@ impl Constraint for AllEqualConstraint {
             for &variable in variables {
                 let mut to_hides: Vec<i32> = Vec::default();
 
-                if !assignments.contains_key(variable) {
-                    if let Some(domain) = domains.get_mut(variable) {
-                        if !domain.values.contains(&single_value) {
+                if !assignments.contains_key(&variable) {
+                    if let Some(domain) = domains.get_mut(&variable) {
+                        if !domain.contains(single_value) {
                             return Ok(false);
                         }
 
-                        for &value in domain.values.iter() {
+                        for value in domain.iter() {
                             if value != single_value {
                                 to_hides.push(value);
                             }
@@ -324,6 +403,11 @@ impl Constraint for AllEqualConstraint {
 
         Ok(true)
     }
+
+    // nothing is filtered out before a value is assigned
+    fn subscriptions(&self, variables: &[VarId]) -> Vec<(VarId, Event)> {
+        variables.iter().map(|&variable| (variable, Event::Assigned)).collect()
+    }
 }
 
 
@@ -343,16 +427,16 @@ impl MaxSumConstraint {
 }
 
 impl Constraint for MaxSumConstraint {
-    fn call<'a>(&self,
-            variables: &[&'a Variable],
-            domains: &mut HashMap<&'a Variable, &mut Domain>,
-            assignments: &mut HashMap<&'a Variable, i32>,
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
             forward_check: bool) -> Result<bool, ConstraintError>
     {
         let max_sum = self.max_value;
         let mut sum = 0;
-        if self.multipliers.is_some() {
-            for (variable, multiplier) in variables.iter().zip(self.multipliers.as_ref().unwrap().clone()).collect::<Vec<(&&Variable, i32)>>()
+        if let Some(multipliers) = self.multipliers.as_ref() {
+            for (variable, multiplier) in variables.iter().zip(multipliers.clone()).collect::<Vec<(&VarId, i32)>>()
             {
                 if assignments.contains_key(variable) {
                     sum += assignments[variable] * multiplier;
@@ -364,12 +448,12 @@ impl Constraint for MaxSumConstraint {
             }
 
             if forward_check {
-                for (variable, multiplier) in variables.iter().zip(self.multipliers.as_ref().unwrap().clone()).collect::<Vec<(&&Variable, i32)>>() {
+                for (variable, multiplier) in variables.iter().zip(multipliers.clone()).collect::<Vec<(&VarId, i32)>>() {
                     if !assignments.contains_key(variable) {
                         if let Some(domain) = domains.get_mut(variable) {
                             let mut to_hides: Vec<i32> = Vec::default();
 
-                            for &value in domain.values.iter() {
+                            for value in domain.iter() {
                                 if sum + value * multiplier > max_sum {
                                     to_hides.push(value);
                                 }
@@ -400,7 +484,7 @@ impl Constraint for MaxSumConstraint {
                         if let Some(domain) = domains.get_mut(variable) {
                             let mut to_hides: Vec<i32> = Vec::default();
 
-                            for &value in domain.values.iter() {
+                            for value in domain.iter() {
                                 if sum + value > max_sum {
                                     to_hides.push(value);
                                 }
@@ -419,49 +503,28 @@ impl Constraint for MaxSumConstraint {
         Ok(true)
     }
 
-    fn pre_process<'a, C>(&self,
-                      variables: &[&'a Variable],
-                      domains: &mut HashMap<&'a Variable, &mut Domain>,
-                      constraints: &mut Vec<&ConstraintVariable<C>>,
-                      vconstraints: &mut HashMap<&'a Variable, Vec<&mut ConstraintVariable<C>>>)
-        where
-            C: Constraint + PartialEq + PartialEq<Self>,
+    fn pre_process(&self,
+                   variables: &[VarId],
+                   domains: &mut Domains) -> bool
     {
-        self.default_pre_process(variables, domains, constraints, vconstraints);
+        let handled = self.default_pre_process(variables, domains);
 
         let max_sum = self.max_value;
-        if self.multipliers.is_some() {
-            for (variable, multiplier) in variables.iter().zip(self.multipliers.as_ref().unwrap().clone()).collect::<Vec<(&&Variable, i32)>>() {
+        if let Some(multipliers) = self.multipliers.as_ref() {
+            for (variable, multiplier) in variables.iter().zip(multipliers.clone()).collect::<Vec<(&VarId, i32)>>() {
                 if let Some(domain) = domains.get_mut(variable) {
-                    let mut to_removes: Vec<usize> = Vec::default();
-
-                    for &value in domain.values.iter() {
-                        if value * multiplier > max_sum {
-                            to_removes.push(domain.values.iter().position(|&v| v == value).unwrap());
-                        }
-                    }
-                    for index in to_removes {
-                        domain.values.remove(index);
-                    }
+                    domain.retain(|value| value * multiplier <= max_sum);
                 }
             }
         } else {
             for variable in variables.iter() {
                 if let Some(domain) = domains.get_mut(variable) {
-                    let mut to_removes: Vec<usize> = Vec::default();
-
-                    for &value in domain.values.iter() {
-                        if value > max_sum {
-                            to_removes.push(domain.values.iter().position(|&v| v == value).unwrap());
-                        }
-                    }
-
-                    for index in to_removes {
-                        domain.values.remove(index);
-                    }
+                    domain.retain(|value| value <= max_sum);
                 }
             }
         }
+
+        handled
     }
 }
 
@@ -482,18 +545,18 @@ impl ExactSumConstraint {
 }
 
 impl Constraint for ExactSumConstraint {
-    fn call<'a>(&self,
-            variables: &[&'a Variable],
-            domains: &mut HashMap<&'a Variable, &mut Domain>,
-            assignments: &mut HashMap<&'a Variable, i32>,
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
             forward_check: bool) -> Result<bool, ConstraintError>
     {
         let exact_sum = self.exact_value;
         let mut sum = 0;
         let mut missing = false;
 
-        if self.multipliers.is_some() {
-            for (variable, multiplier) in variables.iter().zip(self.multipliers.as_ref().unwrap().clone()).collect::<Vec<(&&Variable, i32)>>() {
+        if let Some(multipliers) = self.multipliers.as_ref() {
+            for (variable, multiplier) in variables.iter().zip(multipliers.clone()).collect::<Vec<(&VarId, i32)>>() {
                 if assignments.contains_key(variable) {
                     sum += assignments[variable] * multiplier
                 } else {
@@ -506,12 +569,12 @@ impl Constraint for ExactSumConstraint {
             }
 
             if forward_check && missing {
-                for (variable, multiplier) in variables.iter().zip(self.multipliers.as_ref().unwrap().clone()).collect::<Vec<(&&Variable, i32)>>() {
+                for (variable, multiplier) in variables.iter().zip(multipliers.clone()).collect::<Vec<(&VarId, i32)>>() {
                     if !assignments.contains_key(variable) {
                         if let Some(domain) = domains.get_mut(variable) {
                             let mut to_hides: Vec<i32> = Vec::default();
 
-                            for &value in domain.values.iter() {
+                            for value in domain.iter() {
                                 if sum + value * multiplier > exact_sum {
                                     to_hides.push(value);
                                 }
@@ -521,7 +584,7 @@ impl Constraint for ExactSumConstraint {
                                 domain.hide_value(value);
                             }
 
-                            if domain.values.len() == 0 {
+                            if domain.is_empty() {
                                 return Ok(false);
                             }
                         }
@@ -547,7 +610,7 @@ impl Constraint for ExactSumConstraint {
                         if let Some(domain) = domains.get_mut(variable) {
                             let mut to_hides: Vec<i32> = Vec::default();
 
-                            for &value in domain.values.iter() {
+                            for value in domain.iter() {
                                 if sum + value > exact_sum {
                                     to_hides.push(value);
                                 }
@@ -557,7 +620,7 @@ impl Constraint for ExactSumConstraint {
                                 domain.hide_value(value);
                             }
 
-                            if domain.values.len() == 0 {
+                            if domain.is_empty() {
                                 return Ok(false);
                             }
                         }
@@ -573,51 +636,29 @@ impl Constraint for ExactSumConstraint {
         }
     }
 
-    fn pre_process<'a, C>(&self,
-                      variables: &[&'a Variable],
-                      domains: &mut HashMap<&'a Variable, &mut Domain>,
-                      constraints: &mut Vec<&ConstraintVariable<C>>,
-                      vconstraints: &mut HashMap<&'a Variable, Vec<&mut ConstraintVariable<C>>>)
-        where
-            C: Constraint + PartialEq + PartialEq<Self>,
+    fn pre_process(&self,
+                   variables: &[VarId],
+                   domains: &mut Domains) -> bool
     {
-        self.default_pre_process(variables, domains, constraints, vconstraints);
+        let handled = self.default_pre_process(variables, domains);
 
         let multipliers = self.multipliers.as_ref();
         let exact_sum = self.exact_value;
-        if multipliers.is_some() {
-            for (variable, multiplier) in variables.iter().zip(multipliers.unwrap().clone()).collect::<Vec<(&&Variable, i32)>>() {
+        if let Some(multipliers) = multipliers {
+            for (variable, multiplier) in variables.iter().zip(multipliers.clone()).collect::<Vec<(&VarId, i32)>>() {
                 if let Some(domain) = domains.get_mut(variable) {
-                    let mut to_removes: Vec<usize> = Vec::default();
-
-                    for &value in domain.values.iter() {
-                        if value * multiplier > exact_sum {
-                            to_removes.push(domain.values.iter().position(|&v| v == value).unwrap());
-                        }
-                    }
-
-                    for index in to_removes {
-                        domain.values.remove(index);
-                    }
+                    domain.retain(|value| value * multiplier <= exact_sum);
                 }
             }
         } else {
             for variable in variables.iter() {
                 if let Some(domain) = domains.get_mut(variable) {
-                    let mut to_removes: Vec<usize> = Vec::default();
-
-                    for &value in domain.values.iter() {
-                        if value > exact_sum {
-                            to_removes.push(domain.values.iter().position(|&v| v == value).unwrap());
-                        }
-                    }
-
-                    for index in to_removes {
-                        domain.values.remove(index);
-                    }
+                    domain.retain(|value| value <= exact_sum);
                 }
             }
         }
+
+        handled
     }
 }
 
@@ -638,14 +679,14 @@ impl MinSumConstraint {
 }
 
 impl Constraint for MinSumConstraint {
-    fn call<'a>(&self,
-            variables: &[&'a Variable],
-            _domains: &mut HashMap<&'a Variable, &mut Domain>,
-            assignments: &mut HashMap<&'a Variable, i32>,
+    fn call(&self,
+            variables: &[VarId],
+            _domains: &mut Domains,
+            assignments: &mut Assignments,
             _forward_check: bool) -> Result<bool, ConstraintError>
     {
         for &variable in variables {
-            if !assignments.contains_key(variable) {
+            if !assignments.contains_key(&variable) {
                 return Ok(true);
             }
         }
@@ -653,8 +694,8 @@ impl Constraint for MinSumConstraint {
         let multipliers = self.multipliers.as_ref();
         let min_sum = self.min_value;
         let mut sum = 0;
-        if multipliers.is_some() {
-            for (variable, multiplier) in variables.iter().zip(multipliers.unwrap().clone()).collect::<Vec<(&&Variable, i32)>>() {
+        if let Some(multipliers) = multipliers {
+            for (variable, multiplier) in variables.iter().zip(multipliers.clone()).collect::<Vec<(&VarId, i32)>>() {
                 sum += assignments[variable] * multiplier;
             }
         } else {
@@ -682,46 +723,36 @@ impl InSetConstraint {
 }
 
 impl Constraint for InSetConstraint {
-    fn call<'a>(&self,
-            _variables: &[&'a Variable],
-            _domains: &mut HashMap<&'a Variable, &mut Domain>,
-            _assignments: &mut HashMap<&'a Variable, i32>,
-            _forward_check: bool) -> Result<bool, ConstraintError>
+    // usually handled by the preprocess, but still needed when wrapped by another constraint
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
     {
-        Err(ConstraintError::new("Can't happen because of preprocess."))
+        for &variable in variables {
+            if let Some(&value) = assignments.get(&variable) {
+                if !self.set.contains(&value) {
+                    return Ok(false);
+                }
+            } else if forward_check && !hide_values(variable, domains, assignments, |value| self.set.contains(&value)) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
     }
 
-    fn pre_process<'a, C>(&self,
-                      variables: &[&'a Variable],
-                      domains: &mut HashMap<&'a Variable, &mut Domain>,
-                      constraints: &mut Vec<&ConstraintVariable<C>>,
-                      vconstraints: &mut HashMap<&'a Variable, Vec<&mut ConstraintVariable<C>>>)
-        where
-            C: Constraint + PartialEq + PartialEq<Self>,
+    fn pre_process(&self,
+                   variables: &[VarId],
+                   domains: &mut Domains) -> bool
     {
         for variable in variables {
             if let Some(domain) = domains.get_mut(variable) {
-                let mut to_removes: Vec<usize> = Vec::default();
-                for &value in domain.values.iter() {
-                    if !self.set.contains(&value) {
-                        to_removes.push(domain.values.iter().position(|&v| v == value).unwrap());
-                    }
-                }
-
-                for index in to_removes {
-                    domain.values.remove(index);
-                }
-            }
-            let to_remove = vconstraints[variable].iter().position(|cv| cv.constraint == self && cv.variables == variables);
-            if let Some(index) = to_remove {
-                vconstraints.get_mut(variable).unwrap().remove(index);
+                domain.retain(|value| self.set.contains(&value));
             }
         }
 
-        let to_remove = constraints.iter().position(|cv| cv.constraint == self && cv.variables == variables);
-        if let Some(index) = to_remove {
-            constraints.remove(index);
-        }
+        true
     }
 }
 
@@ -740,47 +771,36 @@ impl NotInSetConstraint {
 }
 
 impl Constraint for NotInSetConstraint {
-    fn call<'a>(&self,
-            _variables: &[&'a Variable],
-            _domains: &mut HashMap<&'a Variable, &mut Domain>,
-            _assignments: &mut HashMap<&'a Variable, i32>,
-            _forward_check: bool) -> Result<bool, ConstraintError>
+    // usually handled by the preprocess, but still needed when wrapped by another constraint
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
     {
-        Err(ConstraintError::new("Can't happen because of preprocess."))
+        for &variable in variables {
+            if let Some(&value) = assignments.get(&variable) {
+                if self.set.contains(&value) {
+                    return Ok(false);
+                }
+            } else if forward_check && !hide_values(variable, domains, assignments, |value| !self.set.contains(&value)) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
     }
 
-    fn pre_process<'a, C>(&self,
-                      variables: &[&'a Variable],
-                      domains: &mut HashMap<&'a Variable, &mut Domain>,
-                      constraints: &mut Vec<&ConstraintVariable<C>>,
-                      vconstraints: &mut HashMap<&'a Variable, Vec<&mut ConstraintVariable<C>>>)
-        where
-            C: Constraint + PartialEq + PartialEq<Self>,
+    fn pre_process(&self,
+                   variables: &[VarId],
+                   domains: &mut Domains) -> bool
     {
         for variable in variables {
             if let Some(domain) = domains.get_mut(variable) {
-                let mut to_removes: Vec<usize> = Vec::default();
-
-                for &value in domain.values.iter() {
-                    if self.set.contains(&value) {
-                        to_removes.push(domain.values.iter().position(|&v| v == value).unwrap());
-                    }
-                }
-
-                for index in to_removes {
-                    domain.values.remove(index);
-                }
+                domain.retain(|value| !self.set.contains(&value));
             }
 
-            let to_remove = vconstraints[variable].iter().position(|cv| cv.constraint == self && cv.variables == variables);
-            if let Some(index) = to_remove {
-                vconstraints.get_mut(variable).unwrap().remove(index);
-            }
-        }
-        let to_remove = constraints.iter().position(|cv| cv.constraint == self && cv.variables == variables);
-        if let Some(index) = to_remove {
-            constraints.remove(index);
         }
+        true
     }
 }
 
@@ -803,10 +823,10 @@ impl SomeInSetConstraint {
 }
 
 impl Constraint for SomeInSetConstraint {
-    fn call<'a>(&self,
-            variables: &[&'a Variable],
-            domains: &mut HashMap<&'a Variable, &mut Domain>,
-            assignments: &mut HashMap<&'a Variable, i32>,
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
             forward_check: bool) -> Result<bool, ConstraintError>
     {
         let mut missing = 0;
@@ -836,7 +856,7 @@ impl Constraint for SomeInSetConstraint {
                         let mut to_hides: Vec<i32> = Vec::default();
 
                         if let Some(domain) = domains.get_mut(variable) {
-                            for &value in domain.values.iter() {
+                            for value in domain.iter() {
                                 if !self.set.contains(&value) {
                                     to_hides.push(value);
                                 }
@@ -886,10 +906,10 @@ impl SomeNotInSetConstraint {
 }
 
 impl Constraint for SomeNotInSetConstraint {
-    fn call<'a>(&self,
-            variables: &[&'a Variable],
-            domains: &mut HashMap<&'a Variable, &mut Domain>,
-            assignments: &mut HashMap<&'a Variable, i32>,
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
             forward_check: bool) -> Result<bool, ConstraintError>
     {
         let mut missing = 0;
@@ -919,7 +939,7 @@ impl Constraint for SomeNotInSetConstraint {
                         let mut to_hides: Vec<i32> = Vec::default();
 
                         if let Some(domain) = domains.get_mut(variable) {
-                            for &value in domain.values.iter() {
+                            for value in domain.iter() {
                                 if self.set.contains(&value) {
                                     to_hides.push(value);
                                 }
@@ -948,4 +968,3631 @@ impl Constraint for SomeNotInSetConstraint {
 
         Ok(true)
     }
-}
\ No newline at end of file
+}
+
+// the reified constraints and connectives tell whether a constraint is entailed or disentailed by
+// trying every combination of the values left, and give up past this many combinations: only a
+// failure the constraint finds by itself on the current domains is still seen then
+const ENUMERATION_LIMIT: usize = 256;
+
+
+fn possible_values(variable: VarId,
+                       domains: &Domains,
+                       assignments: &Assignments) -> Vec<i32> {
+    if let Some(&value) = assignments.get(&variable) {
+        vec![value]
+    } else if let Some(domain) = domains.get(&variable) {
+        domain.values()
+    } else {
+        Vec::default()
+    }
+}
+
+fn hide_values(variable: VarId,
+                   domains: &mut Domains,
+                   assignments: &Assignments,
+                   keep: impl Fn(i32) -> bool) -> bool {
+    if assignments.contains_key(&variable) {
+        return true;
+    }
+
+    if let Some(domain) = domains.get_mut(&variable) {
+        if domain.is_interval() {
+            // only the bounds of an interval move, the values in between are left to the search
+            let first = domain.iter().find(|&value| keep(value));
+            return match (first, domain.max()) {
+                (Some(min), Some(max)) => {
+                    let max = (min..=max).rev().find(|&value| domain.contains(value) && keep(value)).unwrap();
+                    domain.set_min(min);
+                    domain.set_max(max);
+                    true
+                }
+                _ => false,
+            };
+        }
+
+        let to_hides: Vec<i32> = domain.iter().filter(|&value| !keep(value)).collect();
+        for value in to_hides {
+            domain.hide_value(value);
+        }
+        !domain.is_empty()
+    } else {
+        false
+    }
+}
+
+
+/// `array[index] == value`, variables are `[index, value]` when the array is
+/// made of constants, `[index, value, array...]` otherwise.
+#[derive(Debug)]
+pub struct ElementConstraint {
+    array: Option<Vec<i32>>,
+}
+
+impl ElementConstraint {
+    pub fn new(array: Option<Vec<i32>>) -> Self {
+        ElementConstraint {
+            array
+        }
+    }
+}
+
+impl Constraint for ElementConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() < 2 {
+            return Err(ConstraintError::new("Element constraint needs an index and a value variable."));
+        }
+
+        let index = variables[0];
+        let value = variables[1];
+        let size = match self.array.as_ref() {
+            Some(array) => array.len(),
+            None => variables.len() - 2,
+        };
+
+        let value_values: HashSet<i32> = HashSet::from_iter(possible_values(value, domains, assignments));
+        let mut supported_indexes: HashSet<i32> = HashSet::default();
+        let mut supported_values: HashSet<i32> = HashSet::default();
+
+        for i in possible_values(index, domains, assignments) {
+            if i < 0 || i as usize >= size {
+                continue;
+            }
+
+            let element_values = match self.array.as_ref() {
+                Some(array) => vec![array[i as usize]],
+                None => possible_values(variables[i as usize + 2], domains, assignments),
+            };
+
+            for element_value in element_values {
+                if value_values.contains(&element_value) {
+                    supported_indexes.insert(i);
+                    supported_values.insert(element_value);
+                }
+            }
+        }
+
+        if supported_indexes.is_empty() {
+            return Ok(false);
+        }
+
+        if forward_check {
+            if !hide_values(index, domains, assignments, |i| supported_indexes.contains(&i))
+                || !hide_values(value, domains, assignments, |v| supported_values.contains(&v)) {
+                return Ok(false);
+            }
+
+            // once the index is known, the selected element must be equal to the value
+            if self.array.is_none() && supported_indexes.len() == 1 {
+                let &i = supported_indexes.iter().next().unwrap();
+                let element = variables[i as usize + 2];
+                if element != index && element != value
+                    && !hide_values(element, domains, assignments, |v| supported_values.contains(&v)) {
+                    return Ok(false);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+/// The sequence of values taken by the variables must be accepted by the automaton.
+#[derive(Debug)]
+pub struct RegularConstraint {
+    automaton: Automaton,
+}
+
+impl RegularConstraint {
+    pub fn new(automaton: Automaton) -> Self {
+        RegularConstraint {
+            automaton
+        }
+    }
+}
+
+impl Constraint for RegularConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        let layers: Vec<Vec<i32>> = variables.iter()
+            .map(|&variable| possible_values(variable, domains, assignments))
+            .collect();
+
+        // forward pass: states reachable from the initial state
+        let mut forward: Vec<HashSet<usize>> = vec![HashSet::from_iter(vec![self.automaton.initial()])];
+        for values in layers.iter() {
+            let mut next_states: HashSet<usize> = HashSet::default();
+            for &state in forward.last().unwrap() {
+                for &value in values {
+                    if let Some(next) = self.automaton.next(state, value) {
+                        next_states.insert(next);
+                    }
+                }
+            }
+            forward.push(next_states);
+        }
+
+        // backward pass: states from which a final state can be reached
+        let mut backward: HashSet<usize> = forward[layers.len()].iter()
+            .cloned()
+            .filter(|&state| self.automaton.is_final(state))
+            .collect();
+        if backward.is_empty() {
+            return Ok(false);
+        }
+
+        let mut supports: Vec<HashSet<i32>> = vec![HashSet::default(); layers.len()];
+        for (i, values) in layers.iter().enumerate().rev() {
+            let mut previous: HashSet<usize> = HashSet::default();
+            for &state in forward[i].iter() {
+                for &value in values {
+                    if let Some(next) = self.automaton.next(state, value) {
+                        if backward.contains(&next) {
+                            previous.insert(state);
+                            supports[i].insert(value);
+                        }
+                    }
+                }
+            }
+            backward = previous;
+        }
+
+        if forward_check {
+            for (&variable, support) in variables.iter().zip(supports.iter()) {
+                if !hide_values(variable, domains, assignments, |value| support.contains(&value)) {
+                    return Ok(false);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+
+/// Tasks starting at the variables' values, with fixed durations and demands, must never
+/// use more than `capacity` of the resource at the same time.
+///
+/// Starts are pruned by the time-table of the compulsory parts, and an energy check over the
+/// windows between starts and ends rejects overloads. Edge-finding is left out, so some starts
+/// that no schedule uses may be kept until more tasks are fixed.
+#[derive(Debug)]
+pub struct CumulativeConstraint {
+    durations: Vec<i32>,
+    demands: Vec<i32>,
+    capacity: i32,
+}
+
+impl CumulativeConstraint {
+    pub fn new(durations: Vec<i32>, demands: Vec<i32>, capacity: i32) -> Self {
+        CumulativeConstraint {
+            durations,
+            demands,
+            capacity,
+        }
+    }
+
+    // segments (start, end, height) of the profile built from the compulsory parts
+    fn profile(&self, earliest: &[i32], latest: &[i32]) -> Vec<(i32, i32, i32)> {
+        let mut parts: Vec<(i32, i32, i32)> = Vec::default();
+        for i in 0..earliest.len() {
+            if latest[i] < earliest[i] + self.durations[i] && self.demands[i] > 0 {
+                parts.push((latest[i], earliest[i] + self.durations[i], self.demands[i]));
+            }
+        }
+
+        let mut times: Vec<i32> = parts.iter().flat_map(|&(start, end, _)| vec![start, end]).collect();
+        times.sort_unstable();
+        times.dedup();
+
+        let mut segments: Vec<(i32, i32, i32)> = Vec::default();
+        for window in times.windows(2) {
+            let height: i32 = parts.iter()
+                .filter(|&&(start, end, _)| start <= window[0] && window[1] <= end)
+                .map(|&(_, _, demand)| demand)
+                .sum();
+            if height > 0 {
+                segments.push((window[0], window[1], height));
+            }
+        }
+        segments
+    }
+
+    // no set of tasks may need more energy than the resource offers in the window they fit in
+    fn overloaded(&self, earliest: &[i32], latest: &[i32]) -> bool {
+        for &window_start in earliest.iter() {
+            for j in 0..latest.len() {
+                let window_end = latest[j] + self.durations[j];
+                if window_end <= window_start {
+                    continue;
+                }
+
+                let energy: i64 = (0..earliest.len())
+                    .filter(|&i| window_start <= earliest[i] && latest[i] + self.durations[i] <= window_end)
+                    .map(|i| self.durations[i] as i64 * self.demands[i] as i64)
+                    .sum();
+                if energy > self.capacity as i64 * (window_end - window_start) as i64 {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl Constraint for CumulativeConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() != self.durations.len() || variables.len() != self.demands.len() {
+            return Err(ConstraintError::new("Cumulative constraint needs one duration and one demand per variable."));
+        }
+
+        let mut earliest: Vec<i32> = Vec::default();
+        let mut latest: Vec<i32> = Vec::default();
+        for &variable in variables {
+            match bounds(variable, domains, assignments) {
+                Some((min, max)) => {
+                    earliest.push(min as i32);
+                    latest.push(max as i32);
+                }
+                None => return Ok(false),
+            }
+        }
+
+        let segments = self.profile(&earliest, &latest);
+        if segments.iter().any(|&(_, _, height)| height > self.capacity) {
+            return Ok(false);
+        }
+
+        if forward_check {
+            if self.overloaded(&earliest, &latest) {
+                return Ok(false);
+            }
+
+            for (i, &variable) in variables.iter().enumerate() {
+                let duration = self.durations[i];
+                let demand = self.demands[i];
+                let compulsory_start = latest[i];
+                let compulsory_end = earliest[i] + duration;
+
+                // the task's own compulsory part is already counted in the profile
+                let conflict = |start: i32| {
+                    segments.iter()
+                        .filter(|&&(segment_start, segment_end, _)| segment_start < start + duration && start < segment_end)
+                        .find(|&&(segment_start, segment_end, height)| {
+                            let own = compulsory_start <= segment_start && segment_end <= compulsory_end;
+                            !own && height + demand > self.capacity
+                        })
+                        .cloned()
+                };
+
+                // the bounds jump over the overloaded segments, the values in between are only
+                // filtered when the domain can be enumerated
+                let mut first = earliest[i];
+                while let (true, Some((_, segment_end, _))) = (first <= latest[i], conflict(first)) {
+                    first = segment_end;
+                }
+                let mut last = latest[i];
+                while let (true, Some((segment_start, _, _))) = (first <= last, conflict(last)) {
+                    last = segment_start - duration;
+                }
+
+                if !restrict(variable, domains, assignments, (first as i64, last as i64), forward_check)
+                    || !hide_values(variable, domains, assignments, |start| conflict(start).is_none()) {
+                    return Ok(false);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    // the tasks are only pruned from the bounds of the others
+    fn subscriptions(&self, variables: &[VarId]) -> Vec<(VarId, Event)> {
+        variables.iter().map(|&variable| (variable, Event::Bounds)).collect()
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+
+/// Tasks starting at the variables' values, with fixed durations, must not overlap.
+///
+/// Edge-finding, not-first and not-last are applied over every task interval until the bounds
+/// stop moving, each round costing O(n⁴) over the O(n²) task intervals since no Θ-tree is kept.
+#[derive(Debug)]
+pub struct NoOverlapConstraint {
+    durations: Vec<i32>,
+}
+
+impl NoOverlapConstraint {
+    pub fn new(durations: Vec<i32>) -> Self {
+        NoOverlapConstraint {
+            durations
+        }
+    }
+
+    // task intervals: tasks released at or after `est` and due at or before `lct`
+    fn task_interval(&self, earliest: &[i32], latest: &[i32], est: i32, lct: i32) -> Vec<usize> {
+        (0..earliest.len())
+            .filter(|&j| est <= earliest[j] && latest[j] + self.durations[j] <= lct)
+            .collect()
+    }
+
+    fn overloaded(&self, earliest: &[i32], latest: &[i32]) -> bool {
+        for &est in earliest.iter() {
+            for j in 0..latest.len() {
+                let lct = latest[j] + self.durations[j];
+                let omega = self.task_interval(earliest, latest, est, lct);
+                let processing: i32 = omega.iter().map(|&k| self.durations[k]).sum();
+                if !omega.is_empty() && est + processing > lct {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    // one round of edge-finding, not-first and not-last, returns whether a bound moved
+    fn tighten(&self, earliest: &mut [i32], latest: &mut [i32]) -> bool {
+        let mut changed = false;
+        let size = earliest.len();
+
+        for a in 0..size {
+            for b in 0..size {
+                let est_omega = earliest[a];
+                let lct_omega = latest[b] + self.durations[b];
+                let omega = self.task_interval(earliest, latest, est_omega, lct_omega);
+                if omega.is_empty() {
+                    continue;
+                }
+                let processing: i32 = omega.iter().map(|&k| self.durations[k]).sum();
+
+                // earliest completion and latest start of the whole set
+                let ect_omega = omega.iter()
+                    .map(|&k| earliest[k] + omega.iter().filter(|&&l| earliest[l] >= earliest[k]).map(|&l| self.durations[l]).sum::<i32>())
+                    .max()
+                    .unwrap();
+                let lst_omega = omega.iter()
+                    .map(|&k| {
+                        let lct = latest[k] + self.durations[k];
+                        lct - omega.iter().filter(|&&l| latest[l] + self.durations[l] <= lct).map(|&l| self.durations[l]).sum::<i32>()
+                    })
+                    .min()
+                    .unwrap();
+
+                for i in 0..size {
+                    if omega.contains(&i) {
+                        continue;
+                    }
+                    let duration = self.durations[i];
+                    let lct_i = latest[i] + duration;
+
+                    // edge-finding: i must come after (resp. before) every task of omega
+                    if earliest[i].min(est_omega) + processing + duration > lct_omega && ect_omega > earliest[i] {
+                        earliest[i] = ect_omega;
+                        changed = true;
+                    }
+                    if lct_i.max(lct_omega) - processing - duration < est_omega && lst_omega - duration < latest[i] {
+                        latest[i] = lst_omega - duration;
+                        changed = true;
+                    }
+
+                    // not-first: i can't start before every task of omega
+                    if earliest[i] + duration > lct_omega - processing {
+                        let bound = omega.iter().map(|&k| earliest[k] + self.durations[k]).min().unwrap();
+                        if bound > earliest[i] {
+                            earliest[i] = bound;
+                            changed = true;
+                        }
+                    }
+
+                    // not-last: i can't end after every task of omega
+                    if est_omega + processing > latest[i] {
+                        let bound = omega.iter().map(|&k| latest[k]).max().unwrap();
+                        if bound - duration < latest[i] {
+                            latest[i] = bound - duration;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        changed
+    }
+}
+
+impl Constraint for NoOverlapConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() != self.durations.len() {
+            return Err(ConstraintError::new("No-overlap constraint needs one duration per variable."));
+        }
+
+        let mut earliest: Vec<i32> = Vec::default();
+        let mut latest: Vec<i32> = Vec::default();
+        for &variable in variables {
+            match bounds(variable, domains, assignments) {
+                Some((min, max)) => {
+                    earliest.push(min as i32);
+                    latest.push(max as i32);
+                }
+                None => return Ok(false),
+            }
+        }
+
+        if self.overloaded(&earliest, &latest) {
+            return Ok(false);
+        }
+
+        if forward_check {
+            while self.tighten(&mut earliest, &mut latest) {
+                if (0..earliest.len()).any(|i| earliest[i] > latest[i]) || self.overloaded(&earliest, &latest) {
+                    return Ok(false);
+                }
+            }
+
+            for (i, &variable) in variables.iter().enumerate() {
+                if !hide_values(variable, domains, assignments, |start| earliest[i] <= start && start <= latest[i]) {
+                    return Ok(false);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    // the tasks are only pruned from the bounds of the others
+    fn subscriptions(&self, variables: &[VarId]) -> Vec<(VarId, Event)> {
+        variables.iter().map(|&variable| (variable, Event::Bounds)).collect()
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+
+// the `[start, duration, end, presence]` variables of each interval
+fn intervals(variables: &[VarId], name: &'static str) -> Result<Vec<[VarId; 4]>, ConstraintError> {
+    if !variables.chunks_exact(4).remainder().is_empty() {
+        return Err(ConstraintError::new(name));
+    }
+    Ok(variables.chunks(4).map(|chunk| [chunk[0], chunk[1], chunk[2], chunk[3]]).collect())
+}
+
+fn can_take(variable: VarId, value: i32, domains: &Domains, assignments: &Assignments) -> bool {
+    match assignments.get(&variable) {
+        Some(&assigned) => assigned == value,
+        None => domains.get(&variable).is_some_and(|domain| domain.contains(value)),
+    }
+}
+
+// makes the interval present or absent, reports a presence it cannot take as a failure
+fn set_presence(interval: &[VarId; 4],
+                    present: bool,
+                    domains: &mut Domains,
+                    assignments: &Assignments,
+                    forward_check: bool) -> bool {
+    match literal(interval[3], present, domains, assignments) {
+        Some(holds) => holds,
+        None => !forward_check || hide_values(interval[3], domains, assignments, |value| value == present as i32),
+    }
+}
+
+// `x <= y`, keeping the variables within each other's bounds
+fn less_equal(x: VarId,
+                  y: VarId,
+                  domains: &mut Domains,
+                  assignments: &Assignments,
+                  forward_check: bool) -> bool {
+    match (bounds(x, domains, assignments), bounds(y, domains, assignments)) {
+        (Some((x_min, _)), Some((_, y_max))) => {
+            restrict(x, domains, assignments, (i64::MIN, y_max), forward_check)
+                && restrict(y, domains, assignments, (x_min, i64::MAX), forward_check)
+        }
+        _ => false,
+    }
+}
+
+
+/// Variables are `[start, duration, end, presence]`, `start + duration == end` when the interval
+/// is present. An absent interval starts and ends at `earliest` and lasts `shortest`, so that it
+/// only counts once among the solutions.
+///
+/// Added along with each interval variable by `Problem::add_interval_variable`.
+#[derive(Debug)]
+pub struct IntervalConstraint {
+    earliest: i32,
+    shortest: i32,
+}
+
+impl IntervalConstraint {
+    pub fn new(earliest: i32, shortest: i32) -> Self {
+        IntervalConstraint {
+            earliest,
+            shortest,
+        }
+    }
+}
+
+impl Constraint for IntervalConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        let interval = match intervals(variables, "Interval constraint needs a start, a duration, an end and a presence.")?.as_slice() {
+            &[interval] => interval,
+            _ => return Err(ConstraintError::new("Interval constraint needs a start, a duration, an end and a presence.")),
+        };
+        let [start, duration, end, presence] = interval;
+        let sum = LinearConstraint::new(vec![1, 1, -1], Relation::Equal, 0);
+        let absent = [(start, self.earliest), (duration, self.shortest), (end, self.earliest)];
+
+        match literal(presence, true, domains, assignments) {
+            Some(true) => sum.call(&[start, duration, end], domains, assignments, forward_check),
+            Some(false) => Ok(absent.iter().all(|&(variable, value)| {
+                can_take(variable, value, domains, assignments)
+                    && restrict(variable, domains, assignments, (value as i64, value as i64), forward_check)
+            })),
+            None => {
+                // the presence follows from whichever of the two cases is impossible
+                let can_be_present = sum.call(&[start, duration, end], domains, assignments, false)?;
+                let can_be_absent = absent.iter().all(|&(variable, value)| can_take(variable, value, domains, assignments));
+                match (can_be_present, can_be_absent) {
+                    (false, false) => Ok(false),
+                    (false, true) => Ok(set_presence(&interval, false, domains, assignments, forward_check)),
+                    (true, false) => Ok(set_presence(&interval, true, domains, assignments, forward_check)),
+                    (true, true) => Ok(true),
+                }
+            }
+        }
+    }
+
+    // the sum is only bounded from the bounds of the terms
+    fn subscriptions(&self, variables: &[VarId]) -> Vec<(VarId, Event)> {
+        variables.iter().map(|&variable| (variable, Event::Bounds)).collect()
+    }
+}
+
+
+/// Variables are `[start, duration, end, presence]` of two intervals, the second one starts at
+/// least `delay` after the first one ends when both are present.
+#[derive(Debug)]
+pub struct EndBeforeStartConstraint {
+    delay: i32,
+}
+
+impl EndBeforeStartConstraint {
+    pub fn new(delay: i32) -> Self {
+        EndBeforeStartConstraint {
+            delay
+        }
+    }
+}
+
+impl Constraint for EndBeforeStartConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        let (first, second) = match intervals(variables, "End before start constraint needs two intervals.")?.as_slice() {
+            &[first, second] => (first, second),
+            _ => return Err(ConstraintError::new("End before start constraint needs two intervals.")),
+        };
+        let precedence = LinearConstraint::new(vec![1, -1], Relation::LessEqual, -self.delay);
+        let ends = [first[2], second[0]];
+
+        match (literal(first[3], true, domains, assignments), literal(second[3], true, domains, assignments)) {
+            (Some(false), _) | (_, Some(false)) => Ok(true),
+            (Some(true), Some(true)) => precedence.call(&ends, domains, assignments, forward_check),
+            // one interval may still be left out when they cannot follow each other
+            (present, _) => {
+                if precedence.call(&ends, domains, assignments, false)? {
+                    Ok(true)
+                } else if present == Some(true) {
+                    Ok(set_presence(&second, false, domains, assignments, forward_check))
+                } else if literal(second[3], true, domains, assignments) == Some(true) {
+                    Ok(set_presence(&first, false, domains, assignments, forward_check))
+                } else {
+                    Ok(true)
+                }
+            }
+        }
+    }
+}
+
+
+/// Variables are `[start, duration, end, presence]` of each interval, the intervals present do
+/// not overlap.
+///
+/// Pairs of intervals which can only be ordered one way are ordered, an interval which fits neither
+/// before nor after one surely present is left out. No edge-finding is done, unlike
+/// `NoOverlapConstraint` over fixed durations.
+#[derive(Debug, Default)]
+pub struct IntervalNoOverlapConstraint {}
+
+impl IntervalNoOverlapConstraint {
+    pub fn new() -> Self {
+        IntervalNoOverlapConstraint {}
+    }
+}
+
+impl Constraint for IntervalNoOverlapConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        let intervals = intervals(variables, "No-overlap constraint needs four variables per interval.")?;
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 0..intervals.len() {
+                for j in i + 1..intervals.len() {
+                    let (a, b) = (intervals[i], intervals[j]);
+                    let present = (literal(a[3], true, domains, assignments), literal(b[3], true, domains, assignments));
+                    if present.0 == Some(false) || present.1 == Some(false) {
+                        continue;
+                    }
+
+                    let (a_start, a_end, b_start, b_end) = match (bounds(a[0], domains, assignments), bounds(a[2], domains, assignments),
+                                                                  bounds(b[0], domains, assignments), bounds(b[2], domains, assignments)) {
+                        (Some(a_start), Some(a_end), Some(b_start), Some(b_end)) => (a_start, a_end, b_start, b_end),
+                        _ => return Ok(false),
+                    };
+                    let a_first = a_end.0 <= b_start.1;
+                    let b_first = b_end.0 <= a_start.1;
+
+                    let consistent = match (a_first, b_first, present) {
+                        (false, false, (Some(true), Some(true))) => false,
+                        (false, false, (Some(true), None)) if forward_check => set_presence(&b, false, domains, assignments, true),
+                        (false, false, (None, Some(true))) if forward_check => set_presence(&a, false, domains, assignments, true),
+                        (true, false, (Some(true), Some(true))) if forward_check => {
+                            less_equal(a[2], b[0], domains, assignments, true)
+                        }
+                        (false, true, (Some(true), Some(true))) if forward_check => {
+                            less_equal(b[2], a[0], domains, assignments, true)
+                        }
+                        _ => continue,
+                    };
+                    if !consistent {
+                        return Ok(false);
+                    }
+                    // the bounds moved, earlier pairs may now be ordered as well
+                    changed |= [a[0], a[2], b[0], b[2]].iter().map(|&variable| bounds(variable, domains, assignments))
+                        .ne([Some(a_start), Some(a_end), Some(b_start), Some(b_end)].iter().copied())
+                        || literal(a[3], true, domains, assignments) != present.0
+                        || literal(b[3], true, domains, assignments) != present.1;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+/// Variables are `[start, duration, end, presence]` of a main interval followed by its options.
+/// A present main interval runs as exactly one of its options, which are otherwise absent.
+#[derive(Debug, Default)]
+pub struct AlternativeConstraint {}
+
+impl AlternativeConstraint {
+    pub fn new() -> Self {
+        AlternativeConstraint {}
+    }
+}
+
+impl Constraint for AlternativeConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        let intervals = intervals(variables, "Alternative constraint needs four variables per interval.")?;
+        let (main, options) = match intervals.split_first() {
+            Some((main, options)) => (*main, options),
+            None => return Err(ConstraintError::new("Alternative constraint needs a main interval.")),
+        };
+
+        match literal(main[3], true, domains, assignments) {
+            Some(false) => return Ok(options.iter().all(|option| set_presence(option, false, domains, assignments, forward_check))),
+            None if options.iter().any(|option| literal(option[3], true, domains, assignments) == Some(true))
+                && !set_presence(&main, true, domains, assignments, forward_check) => return Ok(false),
+            None if options.iter().all(|option| literal(option[3], true, domains, assignments) == Some(false)) => {
+                return Ok(set_presence(&main, false, domains, assignments, forward_check));
+            }
+            _ => {}
+        }
+        if literal(main[3], true, domains, assignments) != Some(true) {
+            return Ok(true);
+        }
+
+        // an option running elsewhere than the main interval cannot be the one chosen
+        for option in options.iter() {
+            let apart = [(main[0], option[0]), (main[2], option[2])].iter().any(|&(x, y)| {
+                match (bounds(x, domains, assignments), bounds(y, domains, assignments)) {
+                    (Some((x_min, x_max)), Some((y_min, y_max))) => x_max < y_min || y_max < x_min,
+                    _ => true,
+                }
+            });
+            if apart && !set_presence(option, false, domains, assignments, forward_check) {
+                return Ok(false);
+            }
+        }
+
+        let chosen: Vec<&[VarId; 4]> = options.iter().filter(|option| literal(option[3], true, domains, assignments) == Some(true)).collect();
+        let open: Vec<&[VarId; 4]> = options.iter().filter(|option| literal(option[3], true, domains, assignments).is_none()).collect();
+        let chosen = match (chosen.as_slice(), open.as_slice()) {
+            (&[chosen], _) => chosen,
+            (&[], &[only]) => {
+                if !set_presence(only, true, domains, assignments, forward_check) {
+                    return Ok(false);
+                }
+                only
+            }
+            (&[], &[]) => return Ok(false),
+            (&[], _) => return Ok(true),
+            _ => return Ok(false),
+        };
+
+        for option in open.iter().filter(|&&option| option != chosen) {
+            if !set_presence(option, false, domains, assignments, forward_check) {
+                return Ok(false);
+            }
+        }
+        Ok([(main[0], chosen[0]), (main[2], chosen[2])].iter().all(|&(x, y)| {
+            less_equal(x, y, domains, assignments, forward_check) && less_equal(y, x, domains, assignments, forward_check)
+        }))
+    }
+}
+
+
+
+// shared by circuit and subcircuit, variables are the successors of nodes 0..n
+fn circuit_call(subcircuit: bool,
+                    variables: &[VarId],
+                    domains: &mut Domains,
+                    assignments: &mut Assignments,
+                    forward_check: bool) -> Result<bool, ConstraintError>
+{
+    let size = variables.len();
+    let mut successors: Vec<Option<usize>> = vec![None; size];
+    let mut has_predecessor = vec![false; size];
+    let mut taken: HashSet<usize> = HashSet::default();
+    for (node, variable) in variables.iter().enumerate() {
+        if let Some(&value) = assignments.get(variable) {
+            if value < 0 || value as usize >= size || (!subcircuit && value as usize == node) {
+                return Ok(false);
+            }
+            if !taken.insert(value as usize) {
+                return Ok(false);
+            }
+            if value as usize != node {
+                has_predecessor[value as usize] = true;
+            }
+            successors[node] = Some(value as usize);
+        }
+    }
+
+    let in_tour = |node: usize| matches!(successors[node], Some(next) if next != node) || has_predecessor[node];
+
+    // a closed cycle must visit every node, or every node it leaves out must loop on itself
+    for start in 0..size {
+        let mut cycle: Vec<usize> = vec![start];
+        let mut current = start;
+        while let Some(next) = successors[current] {
+            if next == start || cycle.len() > size {
+                break;
+            }
+            cycle.push(next);
+            current = next;
+        }
+        if successors[current] != Some(start) || cycle.len() == 1 {
+            continue;
+        }
+
+        if cycle.len() < size {
+            if !subcircuit {
+                return Ok(false);
+            }
+            for (node, &variable) in variables.iter().enumerate() {
+                if !cycle.contains(&node) {
+                    if in_tour(node) {
+                        return Ok(false);
+                    }
+                    if forward_check && !hide_values(variable, domains, assignments, |value| value as usize == node) {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+    }
+
+    if forward_check {
+        for (node, &variable) in variables.iter().enumerate() {
+            let keep = |value: i32| {
+                value >= 0 && (value as usize) < size
+                    && !taken.contains(&(value as usize))
+                    && (value as usize != node || (subcircuit && !has_predecessor[node]))
+            };
+            if !hide_values(variable, domains, assignments, keep) {
+                return Ok(false);
+            }
+        }
+
+        // closing a chain onto its own head would create a sub-tour
+        for head in 0..size {
+            if has_predecessor[head] || successors[head].is_none() || successors[head] == Some(head) {
+                continue;
+            }
+
+            let mut chain: Vec<usize> = vec![head];
+            let mut tail = head;
+            while let Some(next) = successors[tail] {
+                chain.push(next);
+                tail = next;
+            }
+
+            let premature = if subcircuit {
+                (0..size).any(|node| !chain.contains(&node) && in_tour(node))
+            } else {
+                chain.len() < size
+            };
+            if premature && !hide_values(variables[tail], domains, assignments, |value| value as usize != head) {
+                return Ok(false);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+
+/// The successor variables must form a single cycle going through every node.
+#[derive(Debug, Default)]
+pub struct CircuitConstraint {}
+
+impl CircuitConstraint {
+    pub fn new() -> Self {
+        CircuitConstraint {}
+    }
+}
+
+impl Constraint for CircuitConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        circuit_call(false, variables, domains, assignments, forward_check)
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+/// Like `CircuitConstraint`, but a node may be left out of the cycle by being its own successor.
+#[derive(Debug, Default)]
+pub struct SubcircuitConstraint {}
+
+impl SubcircuitConstraint {
+    pub fn new() -> Self {
+        SubcircuitConstraint {}
+    }
+}
+
+impl Constraint for SubcircuitConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        circuit_call(true, variables, domains, assignments, forward_check)
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+
+// shared by the lexicographic constraints, `xs` must be lexicographically smaller than `ys`
+fn lex_call(strict: bool,
+                xs: &[VarId],
+                ys: &[VarId],
+                domains: &mut Domains,
+                assignments: &mut Assignments,
+                forward_check: bool) -> Result<bool, ConstraintError>
+{
+    if xs.len() != ys.len() {
+        return Err(ConstraintError::new("Lexicographic constraints need vectors of the same size."));
+    }
+
+    let size = xs.len();
+    let x_values: Vec<Vec<i32>> = xs.iter().map(|&x| possible_values(x, domains, assignments)).collect();
+    let y_values: Vec<Vec<i32>> = ys.iter().map(|&y| possible_values(y, domains, assignments)).collect();
+    if x_values.iter().chain(y_values.iter()).any(|values| values.is_empty()) {
+        return Ok(false);
+    }
+
+    let x_min: Vec<i32> = x_values.iter().map(|values| *values.iter().min().unwrap()).collect();
+    let y_max: Vec<i32> = y_values.iter().map(|values| *values.iter().max().unwrap()).collect();
+    let can_be_equal: Vec<bool> = (0..size).map(|i| x_values[i].iter().any(|value| y_values[i].contains(value))).collect();
+
+    // the suffix starting at i can still be ordered when the prefix is equal
+    let mut suffix = vec![!strict; size + 1];
+    for i in (0..size).rev() {
+        suffix[i] = x_min[i] < y_max[i] || (can_be_equal[i] && suffix[i + 1]);
+    }
+    if !suffix[0] {
+        return Ok(false);
+    }
+
+    if forward_check {
+        let mut prefix_equal = true;
+        for i in 0..size {
+            if !prefix_equal {
+                break;
+            }
+
+            let x_support = |value: i32| value < y_max[i] || (y_values[i].contains(&value) && suffix[i + 1]);
+            let y_support = |value: i32| x_min[i] < value || (x_values[i].contains(&value) && suffix[i + 1]);
+            if !hide_values(xs[i], domains, assignments, x_support) || !hide_values(ys[i], domains, assignments, y_support) {
+                return Ok(false);
+            }
+
+            // once a position can be strictly ordered every later value is supported
+            if x_min[i] < y_max[i] {
+                break;
+            }
+            prefix_equal = can_be_equal[i];
+        }
+    }
+
+    Ok(true)
+}
+
+
+/// Variables are `[xs..., ys...]`, `xs` must be lexicographically strictly smaller than `ys`.
+#[derive(Debug, Default)]
+pub struct LexLessConstraint {}
+
+impl LexLessConstraint {
+    pub fn new() -> Self {
+        LexLessConstraint {}
+    }
+}
+
+impl Constraint for LexLessConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        let (xs, ys) = variables.split_at(variables.len() / 2);
+        lex_call(true, xs, ys, domains, assignments, forward_check)
+    }
+}
+
+
+/// Variables are `[xs..., ys...]`, `xs` must be lexicographically smaller than or equal to `ys`.
+#[derive(Debug, Default)]
+pub struct LexLessEqConstraint {}
+
+impl LexLessEqConstraint {
+    pub fn new() -> Self {
+        LexLessEqConstraint {}
+    }
+}
+
+impl Constraint for LexLessEqConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        let (xs, ys) = variables.split_at(variables.len() / 2);
+        lex_call(false, xs, ys, domains, assignments, forward_check)
+    }
+}
+
+
+/// Variables are the rows of a matrix laid out one after the other, each row must be
+/// lexicographically smaller than (or equal to, when not strict) the next one.
+#[derive(Debug)]
+pub struct LexChainConstraint {
+    row_size: usize,
+    strict: bool,
+}
+
+impl LexChainConstraint {
+    pub fn new(row_size: usize, strict: bool) -> Self {
+        LexChainConstraint {
+            row_size,
+            strict,
+        }
+    }
+}
+
+impl Constraint for LexChainConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if self.row_size == 0 || !variables.chunks_exact(self.row_size).remainder().is_empty() {
+            return Err(ConstraintError::new("Lexicographic chain needs complete rows."));
+        }
+
+        let rows: Vec<&[VarId]> = variables.chunks(self.row_size).collect();
+        for pair in rows.windows(2) {
+            if !lex_call(self.strict, pair[0], pair[1], domains, assignments, forward_check)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+
+
+// Some(true) when every completion of the current assignment satisfies the constraint,
+// Some(false) when none does, None when it is still undecided or too costly to tell
+fn evaluate<C: Constraint>(constraint: &C,
+                               variables: &[VarId],
+                               domains: &mut Domains,
+                               assignments: &mut Assignments) -> Result<Option<bool>, ConstraintError>
+{
+    let mut unassigned: Vec<VarId> = Vec::default();
+    for &variable in variables {
+        if !assignments.contains_key(&variable) && !unassigned.contains(&variable) {
+            unassigned.push(variable);
+        }
+    }
+
+    if !constraint.call(variables, domains, assignments, false)? {
+        return Ok(Some(false));
+    }
+    if unassigned.is_empty() {
+        return Ok(Some(true));
+    }
+
+    // counted before listing any value, the domains may be huge ranges
+    let mut combinations: usize = 1;
+    for variable in unassigned.iter() {
+        combinations = combinations.saturating_mul(domains.get(variable).map_or(0, |domain| domain.len()));
+    }
+    if combinations == 0 {
+        return Ok(Some(false));
+    }
+    if combinations > ENUMERATION_LIMIT {
+        return Ok(None);
+    }
+    let candidates: Vec<Vec<i32>> = unassigned.iter()
+        .map(|&variable| possible_values(variable, domains, assignments))
+        .collect();
+
+    let mut satisfied = false;
+    let mut violated = false;
+    for combination in 0..combinations {
+        let mut rest = combination;
+        for (&variable, values) in unassigned.iter().zip(candidates.iter()) {
+            assignments.insert(variable, values[rest % values.len()]);
+            rest /= values.len();
+        }
+
+        if constraint.call(variables, domains, assignments, false)? {
+            satisfied = true;
+        } else {
+            violated = true;
+        }
+        if satisfied && violated {
+            break;
+        }
+    }
+
+    for variable in unassigned {
+        assignments.remove(&variable);
+    }
+
+    Ok(if satisfied && violated { None } else { Some(satisfied) })
+}
+
+// propagate the negation of a constraint, values are only filtered once a single variable is left
+fn call_negation<C: Constraint>(constraint: &C,
+                                    variables: &[VarId],
+                                    domains: &mut Domains,
+                                    assignments: &mut Assignments,
+                                    forward_check: bool) -> Result<bool, ConstraintError>
+{
+    match evaluate(constraint, variables, domains, assignments)? {
+        Some(entailed) => return Ok(!entailed),
+        None if !forward_check => return Ok(true),
+        None => {}
+    }
+
+    let mut unassigned: Vec<VarId> = Vec::default();
+    for &variable in variables {
+        if !assignments.contains_key(&variable) && !unassigned.contains(&variable) {
+            unassigned.push(variable);
+        }
+    }
+
+    if unassigned.len() == 1 {
+        let variable = unassigned[0];
+        let mut to_hides: Vec<i32> = Vec::default();
+        for value in possible_values(variable, domains, assignments) {
+            assignments.insert(variable, value);
+            if constraint.call(variables, domains, assignments, false)? {
+                to_hides.push(value);
+            }
+        }
+        assignments.remove(&variable);
+
+        if !hide_values(variable, domains, assignments, |value| !to_hides.contains(&value)) {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+
+/// Variables are `[b, constraint variables...]`, the constraint holds if and only if `b` is 1.
+///
+/// Whether the constraint holds is only known before `b` is fixed when its unassigned variables
+/// have at most 256 combinations of values left, `b` keeps both values until then. Past that
+/// limit `b` is only set to 0 early when the constraint fails on the current domains, such as
+/// `x <= y` with the smallest `x` above the largest `y`, an entailed constraint leaves it open.
+/// The same goes for the connectives below, which leave a side undecided past that many
+/// combinations.
+#[derive(Debug)]
+pub struct ReifiedConstraint<C: Constraint> {
+    constraint: C,
+}
+
+impl<C: Constraint> ReifiedConstraint<C> {
+    pub fn new(constraint: C) -> Self {
+        ReifiedConstraint {
+            constraint
+        }
+    }
+}
+
+impl<C: Constraint> Constraint for ReifiedConstraint<C> {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.is_empty() {
+            return Err(ConstraintError::new("Reified constraint needs a boolean variable."));
+        }
+
+        let boolean = variables[0];
+        let inner = &variables[1..];
+        let fixed = match possible_values(boolean, domains, assignments).as_slice() {
+            &[value] => Some(value),
+            _ => None,
+        };
+        match fixed {
+            Some(1) => self.constraint.call(inner, domains, assignments, forward_check),
+            Some(0) => call_negation(&self.constraint, inner, domains, assignments, forward_check),
+            Some(_) => Ok(false),
+            None => {
+                if !forward_check {
+                    return Ok(true);
+                }
+                match evaluate(&self.constraint, inner, domains, assignments)? {
+                    Some(entailed) => Ok(hide_values(boolean, domains, assignments, |value| value == entailed as i32)),
+                    None => Ok(hide_values(boolean, domains, assignments, |value| value == 0 || value == 1)),
+                }
+            }
+        }
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+/// Both constraints must hold, variables are `[left variables..., right variables...]`.
+#[derive(Debug)]
+pub struct AndConstraint<L: Constraint, R: Constraint> {
+    left: L,
+    left_size: usize,
+    right: R,
+}
+
+impl<L: Constraint, R: Constraint> AndConstraint<L, R> {
+    pub fn new(left: L, left_size: usize, right: R) -> Self {
+        AndConstraint {
+            left,
+            left_size,
+            right,
+        }
+    }
+}
+
+impl<L: Constraint, R: Constraint> Constraint for AndConstraint<L, R> {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() < self.left_size {
+            return Err(ConstraintError::new("Not enough variables for the left constraint."));
+        }
+
+        let (left_variables, right_variables) = variables.split_at(self.left_size);
+        Ok(self.left.call(left_variables, domains, assignments, forward_check)?
+            && self.right.call(right_variables, domains, assignments, forward_check)?)
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+/// At least one of the constraints must hold, variables are `[left variables..., right variables...]`.
+#[derive(Debug)]
+pub struct OrConstraint<L: Constraint, R: Constraint> {
+    left: L,
+    left_size: usize,
+    right: R,
+}
+
+impl<L: Constraint, R: Constraint> OrConstraint<L, R> {
+    pub fn new(left: L, left_size: usize, right: R) -> Self {
+        OrConstraint {
+            left,
+            left_size,
+            right,
+        }
+    }
+}
+
+impl<L: Constraint, R: Constraint> Constraint for OrConstraint<L, R> {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() < self.left_size {
+            return Err(ConstraintError::new("Not enough variables for the left constraint."));
+        }
+
+        let (left_variables, right_variables) = variables.split_at(self.left_size);
+        let left = evaluate(&self.left, left_variables, domains, assignments)?;
+        let right = evaluate(&self.right, right_variables, domains, assignments)?;
+        match (left, right) {
+            (Some(true), _) | (_, Some(true)) => Ok(true),
+            (Some(false), Some(false)) => Ok(false),
+            // only one side is left to satisfy
+            (Some(false), None) => self.right.call(right_variables, domains, assignments, forward_check),
+            (None, Some(false)) => self.left.call(left_variables, domains, assignments, forward_check),
+            (None, None) => Ok(true),
+        }
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+/// The constraint must not hold.
+#[derive(Debug)]
+pub struct NotConstraint<C: Constraint> {
+    constraint: C,
+}
+
+impl<C: Constraint> NotConstraint<C> {
+    pub fn new(constraint: C) -> Self {
+        NotConstraint {
+            constraint
+        }
+    }
+}
+
+impl<C: Constraint> Constraint for NotConstraint<C> {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        call_negation(&self.constraint, variables, domains, assignments, forward_check)
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+/// When the left constraint holds the right one must hold too, variables are
+/// `[left variables..., right variables...]`.
+#[derive(Debug)]
+pub struct ImpliesConstraint<L: Constraint, R: Constraint> {
+    left: L,
+    left_size: usize,
+    right: R,
+}
+
+impl<L: Constraint, R: Constraint> ImpliesConstraint<L, R> {
+    pub fn new(left: L, left_size: usize, right: R) -> Self {
+        ImpliesConstraint {
+            left,
+            left_size,
+            right,
+        }
+    }
+}
+
+impl<L: Constraint, R: Constraint> Constraint for ImpliesConstraint<L, R> {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() < self.left_size {
+            return Err(ConstraintError::new("Not enough variables for the left constraint."));
+        }
+
+        let (left_variables, right_variables) = variables.split_at(self.left_size);
+        let left = evaluate(&self.left, left_variables, domains, assignments)?;
+        let right = evaluate(&self.right, right_variables, domains, assignments)?;
+        match (left, right) {
+            (Some(false), _) | (_, Some(true)) => Ok(true),
+            (Some(true), Some(false)) => Ok(false),
+            (Some(true), None) => self.right.call(right_variables, domains, assignments, forward_check),
+            (None, Some(false)) => call_negation(&self.left, left_variables, domains, assignments, forward_check),
+            (None, None) => Ok(true),
+        }
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+// Some(true) when the literal `variable == polarity` holds, Some(false) when it no longer can
+fn literal(variable: VarId,
+               polarity: bool,
+               domains: &Domains,
+               assignments: &Assignments) -> Option<bool> {
+    let value = polarity as i32;
+    match (assignments.get(&variable), domains.get(&variable)) {
+        (Some(&assigned), _) => Some(assigned == value),
+        (None, Some(domain)) if domain.contains(value) => if domain.len() == 1 { Some(true) } else { None },
+        _ => Some(false),
+    }
+}
+
+
+/// Clause over boolean variables, at least one of them equals its polarity: `true` for the
+/// variable itself, `false` for its negation.
+///
+/// Two literals which may still hold are watched: the clause is only subscribed to their
+/// variables, and a watch moves to another literal once it fails. As long as two literals may
+/// hold the clause can neither fail nor propagate, so the other variables are not listened to.
+/// The watches are the solver's subscriptions, they need not be restored on backtracking.
+#[derive(Debug)]
+pub struct BoolOrConstraint {
+    polarities: Vec<bool>,
+}
+
+impl BoolOrConstraint {
+    pub fn new(polarities: Vec<bool>) -> Self {
+        BoolOrConstraint {
+            polarities
+        }
+    }
+}
+
+impl Constraint for BoolOrConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() != self.polarities.len() {
+            return Err(ConstraintError::new("Clause needs one polarity per variable."));
+        }
+        if variables.is_empty() {
+            return Ok(false);
+        }
+
+        // stops at the second literal which may hold, the last one must hold
+        let mut unit: Option<usize> = None;
+        for (i, (&variable, &polarity)) in variables.iter().zip(self.polarities.iter()).enumerate() {
+            match literal(variable, polarity, domains, assignments) {
+                Some(true) => return Ok(true),
+                Some(false) => {}
+                None if unit.is_some() => return Ok(true),
+                None => unit = Some(i),
+            }
+        }
+
+        match unit {
+            None => Ok(false),
+            Some(unit) if forward_check => {
+                let polarity = self.polarities[unit] as i32;
+                Ok(hide_values(variables[unit], domains, assignments, |value| value == polarity))
+            }
+            Some(_) => Ok(true),
+        }
+    }
+
+    // a literal only holds or fails once its variable is fixed, the first two are watched
+    fn subscriptions(&self, variables: &[VarId]) -> Vec<(VarId, Event)> {
+        let mut watched: Vec<VarId> = Vec::default();
+        for &variable in variables {
+            if watched.len() < 2 && !watched.contains(&variable) {
+                watched.push(variable);
+            }
+        }
+        watched.into_iter().map(|variable| (variable, Event::Assigned)).collect()
+    }
+
+    // the failed watches move to literals which may still hold, they stay put when none is left
+    fn resubscribe(&self,
+                   variables: &[VarId],
+                   subscriptions: &[(VarId, Event)],
+                   domains: &mut Domains,
+                   assignments: &mut Assignments) -> Option<Vec<(VarId, Event)>> {
+        if variables.len() != self.polarities.len() {
+            return None;
+        }
+
+        let open = |i: usize| literal(variables[i], self.polarities[i], domains, assignments) != Some(false);
+        let watched_open = |watched: VarId| variables.iter().position(|&variable| variable == watched).is_some_and(open);
+        let mut watches: Vec<VarId> = subscriptions.iter().map(|&(variable, _)| variable).filter(|&variable| watched_open(variable)).collect();
+        if watches.len() == subscriptions.len() {
+            return None;
+        }
+
+        for (i, &variable) in variables.iter().enumerate() {
+            if watches.len() == subscriptions.len() {
+                break;
+            }
+            if !watches.contains(&variable) && open(i) {
+                watches.push(variable);
+            }
+        }
+        for &(variable, _) in subscriptions {
+            if watches.len() < subscriptions.len() && !watches.contains(&variable) {
+                watches.push(variable);
+            }
+        }
+        Some(watches.into_iter().map(|variable| (variable, Event::Assigned)).collect())
+    }
+}
+
+
+/// Conjunction of literals over boolean variables, each of them equals its polarity.
+#[derive(Debug)]
+pub struct BoolAndConstraint {
+    polarities: Vec<bool>,
+}
+
+impl BoolAndConstraint {
+    pub fn new(polarities: Vec<bool>) -> Self {
+        BoolAndConstraint {
+            polarities
+        }
+    }
+}
+
+impl Constraint for BoolAndConstraint {
+    // usually handled by the preprocess, but still needed when wrapped by another constraint
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() != self.polarities.len() {
+            return Err(ConstraintError::new("Conjunction needs one polarity per variable."));
+        }
+
+        for (&variable, &polarity) in variables.iter().zip(self.polarities.iter()) {
+            match literal(variable, polarity, domains, assignments) {
+                Some(false) => return Ok(false),
+                None if forward_check && !hide_values(variable, domains, assignments, |value| value == polarity as i32) => {
+                    return Ok(false);
+                }
+                _ => {}
+            }
+        }
+        Ok(true)
+    }
+
+    fn pre_process(&self,
+                   variables: &[VarId],
+                   domains: &mut Domains) -> bool
+    {
+        if variables.len() != self.polarities.len() {
+            return false;
+        }
+
+        for (variable, &polarity) in variables.iter().zip(self.polarities.iter()) {
+            if let Some(domain) = domains.get_mut(variable) {
+                domain.retain(|value| value == polarity as i32);
+            }
+        }
+
+        true
+    }
+}
+
+
+// the elements every set left contains and the elements some set left contains
+fn set_bounds(variable: VarId,
+                  domains: &Domains,
+                  assignments: &Assignments) -> Option<(i32, i32)> {
+    match assignments.get(&variable) {
+        Some(&mask) => Some((mask, mask)),
+        None => domains.get(&variable)?.set_bounds(),
+    }
+}
+
+// keep the sets of the variable between `lower` and `upper`, reports an empty domain as a failure
+fn narrow_set(variable: VarId,
+                  domains: &mut Domains,
+                  assignments: &Assignments,
+                  (lower, upper): (i32, i32),
+                  forward_check: bool) -> bool {
+    let (old_lower, old_upper) = match set_bounds(variable, domains, assignments) {
+        Some(bounds) => bounds,
+        None => return false,
+    };
+    if (lower | old_lower) & !(upper & old_upper) != 0 {
+        return false;
+    }
+
+    if forward_check && !assignments.contains_key(&variable) && (lower & !old_lower != 0 || old_upper & !upper != 0) {
+        let domain = domains.get_mut(&variable).unwrap();
+        domain.include(lower);
+        domain.exclude(!upper);
+        return !domain.is_empty();
+    }
+    true
+}
+
+
+/// Variables are `[a, b]` with the set `a` a subset of `b`.
+#[derive(Debug, Default)]
+pub struct SubsetConstraint {}
+
+impl SubsetConstraint {
+    pub fn new() -> Self {
+        SubsetConstraint {}
+    }
+}
+
+impl Constraint for SubsetConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        let (a, b) = match variables {
+            &[a, b] => (a, b),
+            _ => return Err(ConstraintError::new("Subset constraint needs two variables.")),
+        };
+        let (a_bounds, b_bounds) = match (set_bounds(a, domains, assignments), set_bounds(b, domains, assignments)) {
+            (Some(a_bounds), Some(b_bounds)) => (a_bounds, b_bounds),
+            _ => return Ok(false),
+        };
+
+        Ok(narrow_set(a, domains, assignments, (a_bounds.0, b_bounds.1), forward_check)
+            && narrow_set(b, domains, assignments, (a_bounds.0, b_bounds.1), forward_check))
+    }
+}
+
+
+// shared by union and intersection, variables are `[a, b, c]` with `c == a ∪ b`, or `c == a ∩ b`
+// which is the complement of the union of the complements
+fn union_call(intersection: bool,
+                  variables: &[VarId],
+                  domains: &mut Domains,
+                  assignments: &mut Assignments,
+                  forward_check: bool) -> Result<bool, ConstraintError>
+{
+    if variables.len() != 3 {
+        return Err(ConstraintError::new("Union and intersection constraints need three variables."));
+    }
+
+    let mut bounds: Vec<(i32, i32)> = Vec::default();
+    for &variable in variables {
+        match set_bounds(variable, domains, assignments) {
+            // the complement of [lower, upper] is [!upper, !lower]
+            Some((lower, upper)) => bounds.push(if intersection { (!upper, !lower) } else { (lower, upper) }),
+            None => return Ok(false),
+        }
+    }
+    let [(a_lower, a_upper), (b_lower, b_upper), (c_lower, c_upper)] = [bounds[0], bounds[1], bounds[2]];
+
+    // c holds a and b and nothing else, an element c needs which b cannot give must come from a
+    let c = (c_lower | a_lower | b_lower, c_upper & (a_upper | b_upper));
+    let a = (a_lower | (c.0 & !b_upper), a_upper & c.1);
+    let b = (b_lower | (c.0 & !a_upper), b_upper & c.1);
+
+    for (&variable, (lower, upper)) in variables.iter().zip([a, b, c].iter().cloned()) {
+        let narrowed = if intersection { (!upper, !lower) } else { (lower, upper) };
+        if !narrow_set(variable, domains, assignments, narrowed, forward_check) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+
+/// Variables are `[a, b, c]` with the set `c` the union of `a` and `b`.
+#[derive(Debug, Default)]
+pub struct UnionConstraint {}
+
+impl UnionConstraint {
+    pub fn new() -> Self {
+        UnionConstraint {}
+    }
+}
+
+impl Constraint for UnionConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        union_call(false, variables, domains, assignments, forward_check)
+    }
+}
+
+
+/// Variables are `[a, b, c]` with the set `c` the intersection of `a` and `b`.
+#[derive(Debug, Default)]
+pub struct IntersectionConstraint {}
+
+impl IntersectionConstraint {
+    pub fn new() -> Self {
+        IntersectionConstraint {}
+    }
+}
+
+impl Constraint for IntersectionConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        union_call(true, variables, domains, assignments, forward_check)
+    }
+}
+
+
+/// Variables are `[s, n]` with `n` the number of elements of the set `s`.
+#[derive(Debug, Default)]
+pub struct CardinalityConstraint {}
+
+impl CardinalityConstraint {
+    pub fn new() -> Self {
+        CardinalityConstraint {}
+    }
+}
+
+impl Constraint for CardinalityConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        let (set, n) = match variables {
+            &[set, n] => (set, n),
+            _ => return Err(ConstraintError::new("Cardinality constraint needs a set and a count.")),
+        };
+        let (lower, upper) = match set_bounds(set, domains, assignments) {
+            Some(bounds) => bounds,
+            None => return Ok(false),
+        };
+
+        let (fewest, most) = (lower.count_ones() as i64, upper.count_ones() as i64);
+        if !restrict(n, domains, assignments, (fewest, most), forward_check) {
+            return Ok(false);
+        }
+
+        // the set is decided once the count allows no more than the required elements, or no fewer
+        // than the possible ones
+        match bounds(n, domains, assignments) {
+            Some((min, max)) if assignments.contains_key(&n) || min == max => {
+                if min == fewest {
+                    Ok(narrow_set(set, domains, assignments, (lower, lower), forward_check))
+                } else if min == most {
+                    Ok(narrow_set(set, domains, assignments, (upper, upper), forward_check))
+                } else {
+                    Ok(fewest < min && min < most)
+                }
+            }
+            Some((min, max)) => Ok(min <= most && fewest <= max),
+            None => Ok(false),
+        }
+    }
+}
+
+
+/// The sets taken by the variables are pairwise disjoint.
+#[derive(Debug, Default)]
+pub struct DisjointConstraint {}
+
+impl DisjointConstraint {
+    pub fn new() -> Self {
+        DisjointConstraint {}
+    }
+}
+
+impl Constraint for DisjointConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        let mut bounds: Vec<(i32, i32)> = Vec::default();
+        for &variable in variables {
+            match set_bounds(variable, domains, assignments) {
+                Some(variable_bounds) => bounds.push(variable_bounds),
+                None => return Ok(false),
+            }
+        }
+
+        // no element required by a set may be in another one
+        let mut required = 0;
+        for &(lower, _) in bounds.iter() {
+            if required & lower != 0 {
+                return Ok(false);
+            }
+            required |= lower;
+        }
+
+        for (&variable, &(lower, upper)) in variables.iter().zip(bounds.iter()) {
+            if !narrow_set(variable, domains, assignments, (lower, upper & !(required & !lower)), forward_check) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+
+
+fn bounds(variable: VarId,
+              domains: &Domains,
+              assignments: &Assignments) -> Option<(i64, i64)> {
+    if let Some(&value) = assignments.get(&variable) {
+        Some((value as i64, value as i64))
+    } else {
+        let domain = domains.get(&variable)?;
+        Some((domain.min()? as i64, domain.max()? as i64))
+    }
+}
+
+fn corners(left: (i64, i64), right: (i64, i64), operation: impl Fn(i64, i64) -> i64) -> (i64, i64) {
+    let values = [operation(left.0, right.0), operation(left.0, right.1), operation(left.1, right.0), operation(left.1, right.1)];
+    (*values.iter().min().unwrap(), *values.iter().max().unwrap())
+}
+
+// bounds of `numerator / denominator` over the reals, rounded inwards, the denominator must not contain 0
+fn quotient_bounds(numerator: (i64, i64), denominator: (i64, i64)) -> (i64, i64) {
+    let quotients = [numerator.0 as f64 / denominator.0 as f64, numerator.0 as f64 / denominator.1 as f64,
+                     numerator.1 as f64 / denominator.0 as f64, numerator.1 as f64 / denominator.1 as f64];
+    let min = quotients.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = quotients.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    (min.ceil() as i64, max.floor() as i64)
+}
+
+// `[x, y, z]` with `z == operation(x, y)`: exact filtering once two of the variables are fixed
+fn ternary_call(variables: &[VarId],
+                    domains: &mut Domains,
+                    assignments: &mut Assignments,
+                    forward_check: bool,
+                    operation: impl Fn(i64, i64) -> Option<i64>) -> Result<bool, ConstraintError>
+{
+    let values: Vec<Vec<i32>> = variables.iter().map(|&variable| possible_values(variable, domains, assignments)).collect();
+    if values.iter().any(|values| values.is_empty()) {
+        return Ok(false);
+    }
+
+    let holds = |x: i32, y: i32, z: i32| operation(x as i64, y as i64) == Some(z as i64);
+    match (values[0].as_slice(), values[1].as_slice(), values[2].as_slice()) {
+        (&[x], &[y], &[z]) => Ok(holds(x, y, z)),
+        (&[x], &[y], _) if forward_check => Ok(hide_values(variables[2], domains, assignments, |z| holds(x, y, z))),
+        (&[x], _, &[z]) if forward_check => Ok(hide_values(variables[1], domains, assignments, |y| holds(x, y, z))),
+        (_, &[y], &[z]) if forward_check => Ok(hide_values(variables[0], domains, assignments, |x| holds(x, y, z))),
+        _ => Ok(true),
+    }
+}
+
+// keep the variable's values within `[min, max]`, reports an empty range as a failure
+fn restrict(variable: VarId,
+                domains: &mut Domains,
+                assignments: &Assignments,
+                (min, max): (i64, i64),
+                forward_check: bool) -> bool {
+    let (min, max) = (min.clamp(i32::MIN as i64, i32::MAX as i64) as i32, max.clamp(i32::MIN as i64, i32::MAX as i64) as i32);
+    if let Some(&value) = assignments.get(&variable) {
+        return min <= value && value <= max;
+    }
+
+    match domains.get_mut(&variable) {
+        Some(domain) if forward_check => {
+            domain.set_min(min);
+            domain.set_max(max);
+            !domain.is_empty()
+        }
+        Some(domain) => domain.intersects(min, max),
+        None => false,
+    }
+}
+
+// shared by division and modulo, false once the divisor can only be 0
+fn non_zero_divisor(variable: VarId,
+                        domains: &mut Domains,
+                        assignments: &Assignments,
+                        forward_check: bool) -> bool {
+    match assignments.get(&variable) {
+        Some(&value) => value != 0,
+        None if forward_check => hide_values(variable, domains, assignments, |value| value != 0),
+        None => domains.get(&variable).is_some_and(|domain| domain.iter().any(|value| value != 0)),
+    }
+}
+
+
+/// Variables are `[x, y, z]` with `z == x * y`.
+#[derive(Debug, Default)]
+pub struct ProductConstraint {}
+
+impl ProductConstraint {
+    pub fn new() -> Self {
+        ProductConstraint {}
+    }
+}
+
+impl Constraint for ProductConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() != 3 {
+            return Err(ConstraintError::new("Product constraint needs exactly three variables."));
+        }
+
+        let (x, y, z) = (variables[0], variables[1], variables[2]);
+        if let (Some(x_bounds), Some(y_bounds)) = (bounds(x, domains, assignments), bounds(y, domains, assignments)) {
+            if !restrict(z, domains, assignments, corners(x_bounds, y_bounds, |a, b| a * b), forward_check) {
+                return Ok(false);
+            }
+        }
+
+        if let (Some(x_bounds), Some(y_bounds), Some(z_bounds)) =
+            (bounds(x, domains, assignments), bounds(y, domains, assignments), bounds(z, domains, assignments)) {
+            if (y_bounds.0 > 0 || y_bounds.1 < 0) && !restrict(x, domains, assignments, quotient_bounds(z_bounds, y_bounds), forward_check) {
+                return Ok(false);
+            }
+            if (x_bounds.0 > 0 || x_bounds.1 < 0) && !restrict(y, domains, assignments, quotient_bounds(z_bounds, x_bounds), forward_check) {
+                return Ok(false);
+            }
+        }
+
+        ternary_call(variables, domains, assignments, forward_check, |x, y| Some(x * y))
+    }
+}
+
+
+/// Variables are `[x, y, z]` with `z == x / y`, truncated towards zero, `y` never takes 0.
+#[derive(Debug, Default)]
+pub struct DivisionConstraint {}
+
+impl DivisionConstraint {
+    pub fn new() -> Self {
+        DivisionConstraint {}
+    }
+}
+
+impl Constraint for DivisionConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() != 3 {
+            return Err(ConstraintError::new("Division constraint needs exactly three variables."));
+        }
+
+        let (x, y, z) = (variables[0], variables[1], variables[2]);
+        if !non_zero_divisor(y, domains, assignments, forward_check) {
+            return Ok(false);
+        }
+
+        if let (Some(x_bounds), Some(y_bounds), Some(z_bounds)) =
+            (bounds(x, domains, assignments), bounds(y, domains, assignments), bounds(z, domains, assignments)) {
+            // the quotient is monotonic on each side of zero
+            let mut parts: Vec<(i64, i64)> = Vec::default();
+            if y_bounds.0 < 0 {
+                parts.push((y_bounds.0, y_bounds.1.min(-1)));
+            }
+            if y_bounds.1 > 0 {
+                parts.push((y_bounds.0.max(1), y_bounds.1));
+            }
+
+            let quotients: Vec<(i64, i64)> = parts.iter().map(|&part| corners(x_bounds, part, |a, b| a / b)).collect();
+            let min = quotients.iter().map(|&(min, _)| min).min().unwrap_or(0);
+            let max = quotients.iter().map(|&(_, max)| max).max().unwrap_or(0);
+            if !restrict(z, domains, assignments, (min, max), forward_check) {
+                return Ok(false);
+            }
+
+            // x == z * y + r with |r| < |y|
+            let remainder = y_bounds.0.abs().max(y_bounds.1.abs()) - 1;
+            let (min, max) = corners(z_bounds, y_bounds, |a, b| a * b);
+            if !restrict(x, domains, assignments, (min - remainder, max + remainder), forward_check) {
+                return Ok(false);
+            }
+        }
+
+        ternary_call(variables, domains, assignments, forward_check, |x, y| x.checked_div(y))
+    }
+}
+
+
+/// Variables are `[x, y, z]` with `z == x % y`, the remainder having the sign of `x`, `y` never
+/// takes 0.
+#[derive(Debug, Default)]
+pub struct ModuloConstraint {}
+
+impl ModuloConstraint {
+    pub fn new() -> Self {
+        ModuloConstraint {}
+    }
+}
+
+impl Constraint for ModuloConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() != 3 {
+            return Err(ConstraintError::new("Modulo constraint needs exactly three variables."));
+        }
+
+        let (x, y, z) = (variables[0], variables[1], variables[2]);
+        if !non_zero_divisor(y, domains, assignments, forward_check) {
+            return Ok(false);
+        }
+
+        if let (Some(x_bounds), Some(y_bounds)) = (bounds(x, domains, assignments), bounds(y, domains, assignments)) {
+            // |z| < |y|, |z| <= |x| and z has the sign of x
+            let divisor = y_bounds.0.abs().max(y_bounds.1.abs()) - 1;
+            let min = if x_bounds.0 >= 0 { 0 } else { x_bounds.0.max(-divisor) };
+            let max = if x_bounds.1 <= 0 { 0 } else { x_bounds.1.min(divisor) };
+            if !restrict(z, domains, assignments, (min, max), forward_check) {
+                return Ok(false);
+            }
+        }
+
+        ternary_call(variables, domains, assignments, forward_check, |x, y| x.checked_rem(y))
+    }
+}
+
+
+/// Variables are `[x, z]` with `z == |x|`.
+#[derive(Debug, Default)]
+pub struct AbsConstraint {}
+
+impl AbsConstraint {
+    pub fn new() -> Self {
+        AbsConstraint {}
+    }
+}
+
+impl Constraint for AbsConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() != 2 {
+            return Err(ConstraintError::new("Absolute value constraint needs exactly two variables."));
+        }
+
+        let (x, z) = (variables[0], variables[1]);
+        let absolutes: HashSet<i64> = possible_values(x, domains, assignments).iter().map(|&value| (value as i64).abs()).collect();
+        let z_values: HashSet<i64> = possible_values(z, domains, assignments).iter().map(|&value| value as i64).collect();
+        if absolutes.is_disjoint(&z_values) {
+            return Ok(false);
+        }
+
+        if forward_check {
+            return Ok(hide_values(z, domains, assignments, |value| absolutes.contains(&(value as i64)))
+                && hide_values(x, domains, assignments, |value| z_values.contains(&(value as i64).abs())));
+        }
+        Ok(true)
+    }
+}
+
+
+// shared by min and max, the maximum is handled as the minimum of the opposite values
+fn extremum_call(maximum: bool,
+                     variables: &[VarId],
+                     domains: &mut Domains,
+                     assignments: &mut Assignments,
+                     forward_check: bool) -> Result<bool, ConstraintError>
+{
+    if variables.len() < 2 {
+        return Err(ConstraintError::new("Extremum constraints need at least one variable and a result."));
+    }
+
+    let sign: i64 = if maximum { -1 } else { 1 };
+    let (xs, z) = variables.split_at(variables.len() - 1);
+    let z = z[0];
+
+    let mut x_bounds: Vec<(i64, i64)> = Vec::default();
+    for &x in xs {
+        match bounds(x, domains, assignments) {
+            Some((min, max)) => x_bounds.push(if maximum { (-max, -min) } else { (min, max) }),
+            None => return Ok(false),
+        }
+    }
+    let x_values: Vec<HashSet<i64>> = xs.iter()
+        .map(|&x| possible_values(x, domains, assignments).iter().map(|&value| sign * value as i64).collect())
+        .collect();
+
+    // z can only take a value some x takes while no x is forced below it
+    let smallest_max = x_bounds.iter().map(|&(_, max)| max).min().unwrap();
+    let z_support = |value: i32| {
+        let value = sign * value as i64;
+        value <= smallest_max && x_values.iter().any(|values| values.contains(&value))
+    };
+    let z_values: Vec<i64> = possible_values(z, domains, assignments).into_iter()
+        .filter(|&value| z_support(value))
+        .map(|value| sign * value as i64)
+        .collect();
+    if z_values.is_empty() {
+        return Ok(false);
+    }
+
+    if forward_check {
+        if !hide_values(z, domains, assignments, z_support) {
+            return Ok(false);
+        }
+
+        let z_min = *z_values.iter().min().unwrap();
+        let z_max = *z_values.iter().max().unwrap();
+        let candidates: Vec<usize> = (0..xs.len()).filter(|&i| x_bounds[i].0 <= z_max).collect();
+        for (i, &x) in xs.iter().enumerate() {
+            // the only variable able to reach z must actually reach it
+            let max = if candidates == [i] { z_max } else { i64::MAX };
+            if !hide_values(x, domains, assignments, |value| z_min <= sign * value as i64 && sign * value as i64 <= max) {
+                return Ok(false);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+
+/// Variables are `[xs..., z]` with `z == min(xs)`.
+#[derive(Debug, Default)]
+pub struct MinConstraint {}
+
+impl MinConstraint {
+    pub fn new() -> Self {
+        MinConstraint {}
+    }
+}
+
+impl Constraint for MinConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        extremum_call(false, variables, domains, assignments, forward_check)
+    }
+}
+
+
+/// Variables are `[xs..., z]` with `z == max(xs)`.
+#[derive(Debug, Default)]
+pub struct MaxConstraint {}
+
+impl MaxConstraint {
+    pub fn new() -> Self {
+        MaxConstraint {}
+    }
+}
+
+impl Constraint for MaxConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        extremum_call(true, variables, domains, assignments, forward_check)
+    }
+}
+
+
+
+// shared by count and among, variables are `[xs..., n]` with n the number of xs taking a selected value
+fn among_call(selected: impl Fn(i32) -> bool,
+                  variables: &[VarId],
+                  domains: &mut Domains,
+                  assignments: &mut Assignments,
+                  forward_check: bool) -> Result<bool, ConstraintError>
+{
+    if variables.is_empty() {
+        return Err(ConstraintError::new("Counting constraints need a count variable."));
+    }
+
+    let (xs, n) = variables.split_at(variables.len() - 1);
+    let n = n[0];
+
+    let mut sure: Vec<usize> = Vec::default();
+    let mut possible: Vec<usize> = Vec::default();
+    for (i, &x) in xs.iter().enumerate() {
+        let values = possible_values(x, domains, assignments);
+        if values.iter().all(|&value| selected(value)) {
+            sure.push(i);
+        }
+        if values.iter().any(|&value| selected(value)) {
+            possible.push(i);
+        }
+    }
+
+    let (sure_count, possible_count) = (sure.len() as i64, possible.len() as i64);
+    if !restrict(n, domains, assignments, (sure_count, possible_count), forward_check) {
+        return Ok(false);
+    }
+
+    if forward_check {
+        let (min, max) = bounds(n, domains, assignments).unwrap();
+        for (i, &x) in xs.iter().enumerate() {
+            if sure.contains(&i) || !possible.contains(&i) {
+                continue;
+            }
+
+            // every undecided variable is needed, or none of them may be selected anymore
+            let keep = if possible_count == min {
+                hide_values(x, domains, assignments, &selected)
+            } else if sure_count == max {
+                hide_values(x, domains, assignments, |value| !selected(value))
+            } else {
+                true
+            };
+            if !keep {
+                return Ok(false);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+
+/// Variables are `[xs..., n]`, `n` is the number of xs equal to `value`.
+#[derive(Debug)]
+pub struct CountConstraint {
+    value: i32,
+}
+
+impl CountConstraint {
+    pub fn new(value: i32) -> Self {
+        CountConstraint {
+            value
+        }
+    }
+}
+
+impl Constraint for CountConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        among_call(|value| value == self.value, variables, domains, assignments, forward_check)
+    }
+}
+
+
+/// Variables are `[xs..., n]`, `n` is the number of xs taking a value of the set.
+#[derive(Debug)]
+pub struct AmongConstraint {
+    set: HashSet<i32>,
+}
+
+impl AmongConstraint {
+    pub fn new(set: HashSet<i32>) -> Self {
+        AmongConstraint {
+            set
+        }
+    }
+}
+
+impl Constraint for AmongConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        among_call(|value| self.set.contains(&value), variables, domains, assignments, forward_check)
+    }
+}
+
+
+/// Variables are `[xs..., n]`, `n` is the number of distinct values taken by the xs.
+#[derive(Debug, Default)]
+pub struct NValueConstraint {}
+
+impl NValueConstraint {
+    pub fn new() -> Self {
+        NValueConstraint {}
+    }
+}
+
+impl Constraint for NValueConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.is_empty() {
+            return Err(ConstraintError::new("Counting constraints need a count variable."));
+        }
+
+        let (xs, n) = variables.split_at(variables.len() - 1);
+        let n = n[0];
+
+        let mut fixed_values: HashSet<i32> = HashSet::default();
+        let mut all_values: HashSet<i32> = HashSet::default();
+        let mut unfixed: Vec<VarId> = Vec::default();
+        for &x in xs {
+            let values = possible_values(x, domains, assignments);
+            if values.is_empty() {
+                return Ok(false);
+            }
+            if values.len() == 1 {
+                fixed_values.insert(values[0]);
+            } else {
+                unfixed.push(x);
+            }
+            all_values.extend(values);
+        }
+
+        let fixed_count = fixed_values.len() as i64;
+        let max_count = (all_values.len() as i64).min(fixed_count + unfixed.len() as i64);
+        let min_count = if xs.is_empty() { 0 } else { fixed_count.max(1) };
+        if !restrict(n, domains, assignments, (min_count, max_count), forward_check) {
+            return Ok(false);
+        }
+
+        if forward_check {
+            let (min, max) = bounds(n, domains, assignments).unwrap();
+            let unfixed_count = unfixed.len() as i64;
+            for x in unfixed {
+                // no new value may appear, or every remaining variable must bring a new one
+                let keep = if fixed_count == max {
+                    hide_values(x, domains, assignments, |value| fixed_values.contains(&value))
+                } else if fixed_count + unfixed_count == min {
+                    hide_values(x, domains, assignments, |value| !fixed_values.contains(&value))
+                } else {
+                    true
+                };
+                if !keep {
+                    return Ok(false);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+
+/// Variables are `[xs..., ys...]` with `xs[i] == j` if and only if `ys[j] == i`.
+#[derive(Debug, Default)]
+pub struct InverseConstraint {}
+
+impl InverseConstraint {
+    pub fn new() -> Self {
+        InverseConstraint {}
+    }
+}
+
+impl Constraint for InverseConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() % 2 == 1 {
+            return Err(ConstraintError::new("Inverse constraint needs two vectors of the same size."));
+        }
+
+        let (xs, ys) = variables.split_at(variables.len() / 2);
+        let size = xs.len() as i32;
+
+        // each side is filtered against the other one until nothing changes
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (from, to) in [(xs, ys), (ys, xs)] {
+                let to_values: Vec<Vec<i32>> = to.iter().map(|&variable| possible_values(variable, domains, assignments)).collect();
+                for (i, &variable) in from.iter().enumerate() {
+                    // from[i] == j needs to[j] == i, and no other to[l] already fixed to i
+                    let supported = |j: i32| {
+                        0 <= j && j < size
+                            && to_values[j as usize].contains(&(i as i32))
+                            && to_values.iter().enumerate().all(|(l, values)| l == j as usize || values != &[i as i32])
+                    };
+
+                    let before = possible_values(variable, domains, assignments).len();
+                    let consistent = if forward_check {
+                        hide_values(variable, domains, assignments, supported)
+                    } else {
+                        possible_values(variable, domains, assignments).into_iter().any(supported)
+                    };
+                    if !consistent {
+                        return Ok(false);
+                    }
+                    changed |= possible_values(variable, domains, assignments).len() != before;
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+/// Variables are `[bs..., x]` with `bs[i] == 1` if and only if `x == offset + i`, so that `x`
+/// lies within `[offset, offset + bs.len())` and exactly one of the bs is 1.
+#[derive(Debug)]
+pub struct ChannelingConstraint {
+    offset: i32,
+}
+
+impl ChannelingConstraint {
+    pub fn new(offset: i32) -> Self {
+        ChannelingConstraint {
+            offset
+        }
+    }
+}
+
+impl Constraint for ChannelingConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.is_empty() {
+            return Err(ConstraintError::new("Channeling constraint needs an integer variable."));
+        }
+
+        let (bs, x) = variables.split_at(variables.len() - 1);
+        let x = x[0];
+        let size = bs.len() as i64;
+
+        let b_values: Vec<Vec<i32>> = bs.iter().map(|&b| possible_values(b, domains, assignments)).collect();
+        let can_be = |i: usize, value: i32| b_values[i].contains(&value);
+        let x_support = |value: i32| {
+            let index = value as i64 - self.offset as i64;
+            0 <= index && index < size
+                && can_be(index as usize, 1)
+                && (0..bs.len()).all(|k| k == index as usize || can_be(k, 0))
+        };
+
+        let x_values: Vec<i32> = possible_values(x, domains, assignments).into_iter().filter(|&value| x_support(value)).collect();
+        if x_values.is_empty() {
+            return Ok(false);
+        }
+
+        if forward_check {
+            if !hide_values(x, domains, assignments, x_support) {
+                return Ok(false);
+            }
+
+            for (i, &b) in bs.iter().enumerate() {
+                let target = self.offset + i as i32;
+                let b_support = |value: i32| match value {
+                    0 => x_values.iter().any(|&x_value| x_value != target),
+                    1 => x_values.contains(&target),
+                    _ => false,
+                };
+                if !hide_values(b, domains, assignments, b_support) {
+                    return Ok(false);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+/// Variables are `[bins..., loads...]`: item `i` of size `sizes[i]` goes into bin `bins[i]`
+/// and `loads[b]` is the total size of the items put into bin `b`.
+#[derive(Debug)]
+pub struct BinPackingConstraint {
+    sizes: Vec<i32>,
+}
+
+impl BinPackingConstraint {
+    pub fn new(sizes: Vec<i32>) -> Self {
+        BinPackingConstraint {
+            sizes
+        }
+    }
+}
+
+impl Constraint for BinPackingConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() < self.sizes.len() {
+            return Err(ConstraintError::new("Bin packing constraint needs one bin variable per item."));
+        }
+
+        let (bins, loads) = variables.split_at(self.sizes.len());
+        let bin_count = loads.len() as i32;
+        let total: i64 = self.sizes.iter().map(|&size| size as i64).sum();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            let mut bin_values: Vec<Vec<i32>> = Vec::default();
+            for &bin in bins {
+                if forward_check && !hide_values(bin, domains, assignments, |value| 0 <= value && value < bin_count) {
+                    return Ok(false);
+                }
+                bin_values.push(possible_values(bin, domains, assignments).into_iter().filter(|&value| 0 <= value && value < bin_count).collect());
+            }
+            if bin_values.iter().any(|values| values.is_empty()) {
+                return Ok(false);
+            }
+
+            // loads already packed for sure, and reachable with the undecided items
+            let mut required = vec![0i64; loads.len()];
+            let mut possible = vec![0i64; loads.len()];
+            for (item, values) in bin_values.iter().enumerate() {
+                for &value in values {
+                    possible[value as usize] += self.sizes[item] as i64;
+                }
+                if values.len() == 1 {
+                    required[values[0] as usize] += self.sizes[item] as i64;
+                }
+            }
+
+            let mut load_bounds: Vec<(i64, i64)> = Vec::default();
+            for (b, &load) in loads.iter().enumerate() {
+                if !restrict(load, domains, assignments, (required[b], possible[b]), forward_check) {
+                    return Ok(false);
+                }
+                load_bounds.push(bounds(load, domains, assignments).unwrap());
+            }
+
+            // every item is packed, so the loads must add up to the total size
+            let min_sum: i64 = load_bounds.iter().map(|&(min, _)| min).sum();
+            let max_sum: i64 = load_bounds.iter().map(|&(_, max)| max).sum();
+            if min_sum > total || max_sum < total {
+                return Ok(false);
+            }
+            if !forward_check {
+                break;
+            }
+
+            for (b, &load) in loads.iter().enumerate() {
+                let (min, max) = load_bounds[b];
+                let tightened = ((total - (max_sum - max)).max(min), (total - (min_sum - min)).min(max));
+                if tightened != (min, max) {
+                    if !restrict(load, domains, assignments, tightened, forward_check) {
+                        return Ok(false);
+                    }
+                    load_bounds[b] = tightened;
+                    changed = true;
+                }
+            }
+
+            for (item, &bin) in bins.iter().enumerate() {
+                let values = &bin_values[item];
+                if values.len() == 1 {
+                    continue;
+                }
+
+                let size = self.sizes[item] as i64;
+                // the item would overflow the bin, or the bin can't reach its load without it
+                let needed: Vec<i32> = values.iter().cloned()
+                    .filter(|&value| possible[value as usize] - size < load_bounds[value as usize].0)
+                    .collect();
+                let fits = |value: i32| {
+                    required[value as usize] + size <= load_bounds[value as usize].1
+                        && (needed.is_empty() || needed == [value])
+                };
+
+                if values.iter().any(|&value| !fits(value)) {
+                    if !hide_values(bin, domains, assignments, fits) {
+                        return Ok(false);
+                    }
+                    changed = true;
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+
+// Bounds of the steps of a sequence whose sums over every window must stay in `[min, max]`.
+// The partial sums S_0..S_n form a simple temporal network: each step bounds S_i - S_{i-1} and
+// each window bounds S_{j+window} - S_j, shortest paths then give the tightest bounds, which
+// takes every overlapping window into account at once. None when the network is inconsistent.
+fn sliding_bounds(steps: &[(i64, i64)], window: usize, min: i64, max: i64) -> Option<Vec<(i64, i64)>> {
+    let nodes = steps.len() + 1;
+    let mut distances = vec![vec![i64::MAX; nodes]; nodes];
+    for (node, row) in distances.iter_mut().enumerate() {
+        row[node] = 0;
+    }
+
+    let mut tighten = |from: usize, to: usize, weight: i64| {
+        if weight < distances[from][to] {
+            distances[from][to] = weight;
+        }
+    };
+    for (i, &(step_min, step_max)) in steps.iter().enumerate() {
+        tighten(i, i + 1, step_max);
+        tighten(i + 1, i, -step_min);
+    }
+    if window > 0 {
+        for start in 0..nodes.saturating_sub(window) {
+            tighten(start, start + window, max);
+            tighten(start + window, start, -min);
+        }
+    }
+
+    for k in 0..nodes {
+        for i in 0..nodes {
+            if distances[i][k] == i64::MAX {
+                continue;
+            }
+            for j in 0..nodes {
+                if distances[k][j] != i64::MAX && distances[i][k] + distances[k][j] < distances[i][j] {
+                    distances[i][j] = distances[i][k] + distances[k][j];
+                }
+            }
+        }
+    }
+
+    if (0..nodes).any(|node| distances[node][node] < 0) {
+        return None;
+    }
+    Some((0..steps.len()).map(|i| (-distances[i + 1][i], distances[i][i + 1])).collect())
+}
+
+
+/// The sum of every `window` consecutive variables must be between `min` and `max`.
+#[derive(Debug)]
+pub struct SlidingSumConstraint {
+    window: usize,
+    min: i32,
+    max: i32,
+}
+
+impl SlidingSumConstraint {
+    pub fn new(window: usize, min: i32, max: i32) -> Self {
+        SlidingSumConstraint {
+            window,
+            min,
+            max,
+        }
+    }
+}
+
+impl Constraint for SlidingSumConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        let mut steps: Vec<(i64, i64)> = Vec::default();
+        for &variable in variables {
+            match bounds(variable, domains, assignments) {
+                Some(step) => steps.push(step),
+                None => return Ok(false),
+            }
+        }
+
+        match sliding_bounds(&steps, self.window, self.min as i64, self.max as i64) {
+            None => Ok(false),
+            Some(tightened) => {
+                for (&variable, &step) in variables.iter().zip(tightened.iter()) {
+                    if !restrict(variable, domains, assignments, step, forward_check) {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+/// Every `window` consecutive variables must contain between `min` and `max` values of the set.
+#[derive(Debug)]
+pub struct SequenceConstraint {
+    set: HashSet<i32>,
+    window: usize,
+    min: i32,
+    max: i32,
+}
+
+impl SequenceConstraint {
+    pub fn new(set: HashSet<i32>, window: usize, min: i32, max: i32) -> Self {
+        SequenceConstraint {
+            set,
+            window,
+            min,
+            max,
+        }
+    }
+}
+
+impl Constraint for SequenceConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        // each variable contributes 1 when it takes a value of the set
+        let mut steps: Vec<(i64, i64)> = Vec::default();
+        for &variable in variables {
+            let values = possible_values(variable, domains, assignments);
+            if values.is_empty() {
+                return Ok(false);
+            }
+            let sure = values.iter().all(|value| self.set.contains(value));
+            let possible = values.iter().any(|value| self.set.contains(value));
+            steps.push((sure as i64, possible as i64));
+        }
+
+        match sliding_bounds(&steps, self.window, self.min as i64, self.max as i64) {
+            None => Ok(false),
+            Some(tightened) => {
+                if forward_check {
+                    for (&variable, &(min, max)) in variables.iter().zip(tightened.iter()) {
+                        let keep = |value: i32| {
+                            let inside = self.set.contains(&value) as i64;
+                            min <= inside && inside <= max
+                        };
+                        if !hide_values(variable, domains, assignments, keep) {
+                            return Ok(false);
+                        }
+                    }
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Expensive
+    }
+}
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    LessEqual,
+    Equal,
+    GreaterEqual,
+    NotEqual,
+}
+
+pub(crate) fn floor_div(numerator: i64, denominator: i64) -> i64 {
+    let quotient = numerator / denominator;
+    if numerator % denominator != 0 && (numerator < 0) != (denominator < 0) { quotient - 1 } else { quotient }
+}
+
+pub(crate) fn ceil_div(numerator: i64, denominator: i64) -> i64 {
+    let quotient = numerator / denominator;
+    if numerator % denominator != 0 && (numerator < 0) == (denominator < 0) { quotient + 1 } else { quotient }
+}
+
+
+/// `sum(coefficients[i] * variables[i]) <relation> constant`, coefficients may be negative.
+#[derive(Debug)]
+pub struct LinearConstraint {
+    coefficients: Vec<i32>,
+    relation: Relation,
+    constant: i32,
+}
+
+impl LinearConstraint {
+    pub fn new(coefficients: Vec<i32>, relation: Relation, constant: i32) -> Self {
+        LinearConstraint {
+            coefficients,
+            relation,
+            constant,
+        }
+    }
+}
+
+impl Constraint for LinearConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() != self.coefficients.len() {
+            return Err(ConstraintError::new("Linear constraint needs one coefficient per variable."));
+        }
+
+        let constant = self.constant as i64;
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            let mut terms: Vec<(i64, i64)> = Vec::default();
+            for (&variable, &coefficient) in variables.iter().zip(self.coefficients.iter()) {
+                let (min, max) = match bounds(variable, domains, assignments) {
+                    Some(bounds) => bounds,
+                    None => return Ok(false),
+                };
+                let coefficient = coefficient as i64;
+                terms.push(if coefficient >= 0 { (coefficient * min, coefficient * max) } else { (coefficient * max, coefficient * min) });
+            }
+            let total_min: i64 = terms.iter().map(|&(min, _)| min).sum();
+            let total_max: i64 = terms.iter().map(|&(_, max)| max).sum();
+
+            let (upper, lower) = match self.relation {
+                Relation::LessEqual => (total_min <= constant, true),
+                Relation::GreaterEqual => (true, total_max >= constant),
+                Relation::Equal => (total_min <= constant, total_max >= constant),
+                Relation::NotEqual => (true, !(total_min == constant && total_max == constant)),
+            };
+            if !upper || !lower {
+                return Ok(false);
+            }
+            if !forward_check {
+                break;
+            }
+
+            if self.relation == Relation::NotEqual {
+                // only the last undecided variable can be filtered
+                let undecided: Vec<usize> = (0..terms.len()).filter(|&i| terms[i].0 != terms[i].1).collect();
+                if let [i] = undecided.as_slice() {
+                    // at most one value makes the sum equal the constant
+                    let rest = total_min - terms[*i].0;
+                    let coefficient = self.coefficients[*i] as i64;
+                    if (constant - rest) % coefficient == 0 {
+                        let value = (constant - rest) / coefficient;
+                        let domain = domains.get_mut(&variables[*i]).unwrap();
+                        if i32::try_from(value).is_ok_and(|value| domain.contains(value)) {
+                            domain.hide_value(value as i32);
+                            if domain.is_empty() {
+                                return Ok(false);
+                            }
+                        }
+                    }
+                }
+                break;
+            }
+
+            for (i, &variable) in variables.iter().enumerate() {
+                let coefficient = self.coefficients[i] as i64;
+                if coefficient == 0 {
+                    continue;
+                }
+
+                let (mut min, mut max) = bounds(variable, domains, assignments).unwrap();
+                let (old_min, old_max) = (min, max);
+                if self.relation != Relation::GreaterEqual {
+                    // coefficient * x <= constant - the smallest the others can be
+                    let limit = constant - (total_min - terms[i].0);
+                    if coefficient > 0 { max = max.min(floor_div(limit, coefficient)) } else { min = min.max(ceil_div(limit, coefficient)) }
+                }
+                if self.relation != Relation::LessEqual {
+                    let limit = constant - (total_max - terms[i].1);
+                    if coefficient > 0 { min = min.max(ceil_div(limit, coefficient)) } else { max = max.min(floor_div(limit, coefficient)) }
+                }
+
+                if (min, max) != (old_min, old_max) {
+                    if !restrict(variable, domains, assignments, (min, max), forward_check) {
+                        return Ok(false);
+                    }
+                    changed = true;
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    // the sum is only bounded from the bounds of the terms
+    fn subscriptions(&self, variables: &[VarId]) -> Vec<(VarId, Event)> {
+        variables.iter().map(|&variable| (variable, Event::Bounds)).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Domain;
+    use crate::problem::{BacktrackingSolver, Problem};
+
+    // every tuple made of one value of each domain
+    fn tuples(domains: &[Vec<i32>]) -> Vec<Vec<i32>> {
+        domains.iter().fold(vec![Vec::default()], |tuples, domain| {
+            tuples.iter()
+                .flat_map(|tuple| domain.iter().map(move |&value| {
+                    let mut tuple = tuple.clone();
+                    tuple.push(value);
+                    tuple
+                }))
+                .collect()
+        })
+    }
+
+    // the solutions found with and without forward checking must be exactly the tuples accepted by `check`
+    fn check_solutions<C: Constraint + 'static>(constraint: impl Fn() -> C,
+                                                domains: &[Vec<i32>],
+                                                check: impl Fn(&[i32]) -> bool) {
+        let expected: Vec<Vec<i32>> = tuples(domains).into_iter().filter(|tuple| check(tuple)).collect();
+        for &forward_check in [true, false].iter() {
+            let mut problem = Problem::with_solver(Box::new(BacktrackingSolver::new(forward_check)));
+            let variables: Vec<VarId> = domains.iter().map(|domain| problem.add_variable("x", domain)).collect();
+            problem.add_constraint(constraint(), &variables);
+
+            let mut found: Vec<Vec<i32>> = problem.get_solutions().unwrap().iter()
+                .map(|solution| variables.iter().map(|variable| solution[variable]).collect())
+                .collect();
+            found.sort();
+            assert_eq!(found, expected, "forward_check: {}", forward_check);
+        }
+    }
+
+    // domains left by a single forward checking call, the variables with a single value being
+    // assigned as the search would have done, None when the constraint fails
+    fn propagate<C: Constraint>(constraint: &C, domains: &[Vec<i32>]) -> Option<Vec<Vec<i32>>> {
+        let mut assignments = Assignments::new();
+        let variables: Vec<VarId> = (0..domains.len()).map(VarId::new).collect();
+        for (&variable, values) in variables.iter().zip(domains.iter()) {
+            if values.len() == 1 {
+                assignments.insert(variable, values[0]);
+            }
+        }
+        let mut domains = Domains::new(domains.iter().map(|values| Domain::new(values)).collect());
+        if !constraint.call(&variables, &mut domains, &mut assignments, true).unwrap() {
+            return None;
+        }
+        Some(variables.iter()
+            .map(|variable| {
+                let mut values = domains.get(variable).unwrap().values();
+                values.sort_unstable();
+                values
+            })
+            .collect())
+    }
+
+    #[test]
+    fn element_over_constants() {
+        let array = vec![3, 1, 3, 7];
+        check_solutions(|| ElementConstraint::new(Some(array.clone())),
+                        &[(-2..6).collect(), (0..9).collect()],
+                        |t| t[0] >= 0 && (t[0] as usize) < array.len() && array[t[0] as usize] == t[1]);
+    }
+
+    #[test]
+    fn element_over_variables() {
+        let values: Vec<i32> = (0..3).collect();
+        check_solutions(|| ElementConstraint::new(None),
+                        &[(-1..4).collect(), values.clone(), values.clone(), values.clone(), values],
+                        |t| (0..3).contains(&t[0]) && t[t[0] as usize + 2] == t[1]);
+    }
+
+    #[test]
+    fn element_keeps_every_index_of_a_repeated_value() {
+        let constraint = ElementConstraint::new(Some(vec![3, 1, 3, 7]));
+        let domains = propagate(&constraint, &[(-3..8).collect(), vec![3, 4]]).unwrap();
+        assert_eq!(domains, vec![vec![0, 2], vec![3]]);
+    }
+
+    #[test]
+    fn element_fails_when_every_index_is_out_of_range() {
+        let constraint = ElementConstraint::new(Some(vec![3, 1]));
+        assert_eq!(propagate(&constraint, &[vec![-1, 2, 5], vec![1, 3]]), None);
+        let domains = propagate(&constraint, &[vec![-1, 1, 2], vec![1, 3]]).unwrap();
+        assert_eq!(domains, vec![vec![1], vec![1]]);
+    }
+
+    // words over {0, 1, 2} without two consecutive 1s and not ending with 2
+    fn no_consecutive_ones() -> Automaton {
+        let mut transitions = HashMap::default();
+        for &(state, value, next) in [(0, 0, 0), (0, 1, 1), (0, 2, 2), (1, 0, 0), (1, 2, 2), (2, 0, 0), (2, 1, 1), (2, 2, 2)].iter() {
+            transitions.insert((state, value), next);
+        }
+        Automaton::new(0, HashSet::from_iter(vec![0, 1]), transitions)
+    }
+
+    #[test]
+    fn regular_accepts_the_automaton_words() {
+        let automaton = no_consecutive_ones();
+        check_solutions(|| RegularConstraint::new(no_consecutive_ones()),
+                        &vec![vec![0, 1, 2, 3]; 4],
+                        |t| automaton.accepts(t));
+    }
+
+    #[test]
+    fn regular_keeps_supported_values_only() {
+        let constraint = RegularConstraint::new(no_consecutive_ones());
+        let domains = propagate(&constraint, &[vec![1], vec![0, 1, 2], vec![1, 2]]).unwrap();
+        assert_eq!(domains, vec![vec![1], vec![0, 2], vec![1]]);
+    }
+
+    // tasks as (start, duration, demand) never use more than `capacity` at once
+    fn fits(tasks: &[(i32, i32, i32)], capacity: i32) -> bool {
+        let end = tasks.iter().map(|&(start, duration, _)| start + duration).max().unwrap_or(0);
+        (0..end).all(|time| {
+            tasks.iter()
+                .filter(|&&(start, duration, _)| start <= time && time < start + duration)
+                .map(|&(_, _, demand)| demand)
+                .sum::<i32>() <= capacity
+        })
+    }
+
+    #[test]
+    fn cumulative_matches_brute_force() {
+        let (durations, demands) = (vec![2, 3, 1, 2], vec![2, 1, 2, 3]);
+        check_solutions(|| CumulativeConstraint::new(durations.clone(), demands.clone(), 3),
+                        &vec![(0..5).collect(); 4],
+                        |t| {
+                            let tasks: Vec<(i32, i32, i32)> = (0..4).map(|i| (t[i], durations[i], demands[i])).collect();
+                            fits(&tasks, 3)
+                        });
+    }
+
+    #[test]
+    fn cumulative_pushes_starts_past_compulsory_parts() {
+        // the first task surely runs over [2, 4), the second one can't overlap it
+        let constraint = CumulativeConstraint::new(vec![3, 2], vec![2, 2], 3);
+        let domains = propagate(&constraint, &[vec![1, 2], (1..7).collect()]).unwrap();
+        assert_eq!(domains, vec![vec![1, 2], vec![4, 5, 6]]);
+    }
+
+    #[test]
+    fn no_overlap_matches_brute_force() {
+        let durations = vec![2, 3, 1, 2];
+        check_solutions(|| NoOverlapConstraint::new(durations.clone()),
+                        &vec![(0..8).collect(); 4],
+                        |t| (0..4).all(|i| (0..i).all(|j| t[i] + durations[i] <= t[j] || t[j] + durations[j] <= t[i])));
+    }
+
+    #[test]
+    fn no_overlap_applies_not_first_and_not_last() {
+        // the last task can't run first among the other two, it can only start once the first
+        // of them ended: 7 rather than 6
+        let constraint = NoOverlapConstraint::new(vec![4, 2, 1]);
+        let domains = propagate(&constraint, &[(3..7).collect(), (0..4).collect(), (6..11).collect()]).unwrap();
+        assert_eq!(domains, vec![(3..7).collect::<Vec<i32>>(), (0..4).collect(), (7..11).collect()]);
+
+        // the mirror image, where the last task can't run last
+        let constraint = NoOverlapConstraint::new(vec![4, 2, 1]);
+        let domains = propagate(&constraint, &[(1..5).collect(), (6..10).collect(), (0..5).collect()]).unwrap();
+        assert_eq!(domains, vec![(1..5).collect::<Vec<i32>>(), (6..10).collect(), (0..4).collect()]);
+    }
+
+    // `[start, duration, end, presence]` of each interval, laid end to end
+    fn interval_domains(count: usize) -> Vec<Vec<i32>> {
+        (0..count).flat_map(|_| vec![vec![0, 1, 2], vec![1, 2], vec![1, 2, 3], vec![0, 1]]).collect()
+    }
+
+    #[test]
+    fn interval_sums_present_and_pins_absent() {
+        check_solutions(|| IntervalConstraint::new(0, 1),
+                        &[(0..4).collect(), vec![1, 2], (0..4).collect(), vec![0, 1]],
+                        |t| if t[3] == 1 { t[0] + t[1] == t[2] } else { t[..3] == [0, 1, 0] });
+    }
+
+    #[test]
+    fn end_before_start_matches_brute_force() {
+        check_solutions(|| EndBeforeStartConstraint::new(1),
+                        &interval_domains(2),
+                        |t| t[3] == 0 || t[7] == 0 || t[2] < t[4]);
+    }
+
+    #[test]
+    fn interval_no_overlap_matches_brute_force() {
+        check_solutions(IntervalNoOverlapConstraint::new,
+                        &interval_domains(3),
+                        |t| (0..3).all(|i| (0..i).all(|j| {
+                            let (a, b) = (&t[4 * i..4 * i + 4], &t[4 * j..4 * j + 4]);
+                            a[3] == 0 || b[3] == 0 || a[2] <= b[0] || b[2] <= a[0]
+                        })));
+    }
+
+    #[test]
+    fn interval_no_overlap_orders_and_leaves_out() {
+        // the first interval takes [0, 2), the second one can only follow it
+        let domains = propagate(&IntervalNoOverlapConstraint::new(),
+                                &[vec![0], vec![2], vec![2], vec![1], (0..5).collect(), vec![1], (1..6).collect(), vec![1]]).unwrap();
+        assert_eq!(domains[4], vec![2, 3, 4]);
+
+        // an optional interval with no room left is left out
+        let domains = propagate(&IntervalNoOverlapConstraint::new(),
+                                &[vec![0], vec![4], vec![4], vec![1], (1..3).collect(), vec![1], (2..4).collect(), vec![0, 1]]).unwrap();
+        assert_eq!(domains[7], vec![0]);
+    }
+
+    #[test]
+    fn alternative_matches_brute_force() {
+        check_solutions(AlternativeConstraint::new,
+                        &interval_domains(3),
+                        |t| if t[3] == 0 {
+                            t[7] == 0 && t[11] == 0
+                        } else {
+                            let chosen: Vec<&[i32]> = t[4..].chunks(4).filter(|option| option[3] == 1).collect();
+                            chosen.len() == 1 && chosen[0][0] == t[0] && chosen[0][2] == t[2]
+                        });
+    }
+
+    // the successors form a permutation whose nodes not looping on themselves make a single cycle
+    fn single_cycle(successors: &[i32], subcircuit: bool) -> bool {
+        let size = successors.len();
+        if successors.iter().any(|&next| next < 0 || next as usize >= size) {
+            return false;
+        }
+        let mut sorted = Vec::from(successors);
+        sorted.sort_unstable();
+        if sorted != (0..size as i32).collect::<Vec<i32>>() {
+            return false;
+        }
+
+        let moving: Vec<usize> = (0..size).filter(|&node| successors[node] as usize != node).collect();
+        if moving.len() < size && !subcircuit {
+            return false;
+        }
+        let start = match moving.first() {
+            Some(&start) => start,
+            None => return true,
+        };
+        let mut length = 1;
+        let mut current = successors[start] as usize;
+        while current != start {
+            length += 1;
+            current = successors[current] as usize;
+        }
+        length == moving.len()
+    }
+
+    #[test]
+    fn circuit_matches_brute_force() {
+        check_solutions(CircuitConstraint::new, &vec![(-1..5).collect(); 4], |t| single_cycle(t, false));
+        check_solutions(CircuitConstraint::new, &vec![(0..5).collect(); 5], |t| single_cycle(t, false));
+    }
+
+    #[test]
+    fn subcircuit_matches_brute_force() {
+        check_solutions(SubcircuitConstraint::new, &vec![(-1..5).collect(); 4], |t| single_cycle(t, true));
+        check_solutions(SubcircuitConstraint::new, &vec![(0..5).collect(); 5], |t| single_cycle(t, true));
+    }
+
+    #[test]
+    fn circuit_forbids_closing_a_sub_tour() {
+        let mut domains = vec![vec![1], vec![2], (0..4).collect(), (0..4).collect()];
+        let domains_left = propagate(&CircuitConstraint::new(), &domains).unwrap();
+        assert_eq!(domains_left[2], vec![3]);
+
+        // a subcircuit may close early, as long as the other nodes loop on themselves
+        domains[2] = (0..4).collect();
+        let domains_left = propagate(&SubcircuitConstraint::new(), &domains).unwrap();
+        assert_eq!(domains_left[2], vec![0, 3]);
+    }
+
+    #[test]
+    fn lex_matches_brute_force() {
+        let domains = vec![vec![0, 1, 2]; 4];
+        check_solutions(LexLessConstraint::new, &domains, |t| t[..2] < t[2..]);
+        check_solutions(LexLessEqConstraint::new, &domains, |t| t[..2] <= t[2..]);
+        check_solutions(|| LexChainConstraint::new(2, true), &vec![vec![0, 1, 2]; 6], |t| t[..2] < t[2..4] && t[2..4] < t[4..]);
+        check_solutions(|| LexChainConstraint::new(2, false), &vec![vec![0, 1]; 6], |t| t[..2] <= t[2..4] && t[2..4] <= t[4..]);
+    }
+
+    #[test]
+    fn lex_prunes_the_first_undecided_position() {
+        // x0 == y0, so x1 must be strictly smaller than y1
+        let domains = propagate(&LexLessConstraint::new(), &[vec![1], vec![1, 2, 3], vec![1], vec![0, 1, 2]]).unwrap();
+        assert_eq!(domains, vec![vec![1], vec![1], vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn lex_chain_needs_complete_rows() {
+        let mut domains = Domains::new(vec![Domain::new(&[0, 1]); 3]);
+        let variables: Vec<VarId> = (0..3).map(VarId::new).collect();
+        assert!(LexChainConstraint::new(2, false).call(&variables, &mut domains, &mut Assignments::new(), true).is_err());
+    }
+
+    #[test]
+    fn reified_matches_brute_force() {
+        check_solutions(|| ReifiedConstraint::new(AllDifferentConstraint::new()),
+                        &[vec![0, 1], vec![1, 2, 3], vec![2, 3]],
+                        |t| (t[0] == 1) == (t[1] != t[2]));
+    }
+
+    #[test]
+    fn reified_fixes_the_boolean_once_entailed() {
+        let constraint = ReifiedConstraint::new(AllDifferentConstraint::new());
+        let domains = propagate(&constraint, &[vec![0, 1], vec![1], vec![2, 3]]).unwrap();
+        assert_eq!(domains[0], vec![1]);
+        let domains = propagate(&constraint, &[vec![0, 1], vec![2], vec![2]]).unwrap();
+        assert_eq!(domains[0], vec![0]);
+
+        // past the enumeration limit the boolean stays undecided, even though the constraint
+        // can't hold anymore
+        let constraint = ReifiedConstraint::new(AllEqualConstraint::new());
+        let wide: Vec<i32> = (0..20).collect();
+        let domains = propagate(&constraint, &[vec![0, 1], vec![-1], wide.clone(), wide]).unwrap();
+        assert_eq!(domains[0], vec![0, 1]);
+
+    }
+
+    #[test]
+    fn reified_past_the_enumeration_limit() {
+        // 21 * 21 combinations: x <= y is entailed yet the boolean stays undecided, while a
+        // violation the linear bounds detect on their own still fixes it
+        let constraint = ReifiedConstraint::new(LinearConstraint::new(vec![1, -1], Relation::LessEqual, 0));
+        let domains = propagate(&constraint, &[vec![0, 1], (0..21).collect(), (20..41).collect()]).unwrap();
+        assert_eq!(domains[0], vec![0, 1]);
+        let domains = propagate(&constraint, &[vec![0, 1], (21..42).collect(), (0..21).collect()]).unwrap();
+        assert_eq!(domains[0], vec![0]);
+
+        // the boolean is still checked once assigned
+        let wide: Vec<i32> = (0..21).collect();
+        check_solutions(|| ReifiedConstraint::new(LinearConstraint::new(vec![1, -1], Relation::LessEqual, 0)),
+                        &[vec![0, 1], wide.clone(), wide],
+                        |t| (t[0] == 1) == (t[1] <= t[2]));
+    }
+
+    #[test]
+    fn connectives_match_brute_force() {
+        let domains = vec![vec![0, 1, 2]; 4];
+        let different = |t: &[i32]| t[0] != t[1];
+        let equal = |t: &[i32]| t[0] == t[1];
+        check_solutions(|| AndConstraint::new(AllDifferentConstraint::new(), 2, AllEqualConstraint::new()),
+                        &domains, |t| different(&t[..2]) && equal(&t[2..]));
+        check_solutions(|| OrConstraint::new(AllDifferentConstraint::new(), 2, AllEqualConstraint::new()),
+                        &domains, |t| different(&t[..2]) || equal(&t[2..]));
+        check_solutions(|| NotConstraint::new(AllDifferentConstraint::new()),
+                        &domains[..3], |t| !(t[0] != t[1] && t[1] != t[2] && t[0] != t[2]));
+        check_solutions(|| ImpliesConstraint::new(AllDifferentConstraint::new(), 2, AllEqualConstraint::new()),
+                        &domains, |t| !different(&t[..2]) || equal(&t[2..]));
+    }
+
+    #[test]
+    fn connectives_propagate_the_side_left() {
+        // the left side is violated, so the right one must hold
+        let or = OrConstraint::new(AllDifferentConstraint::new(), 2, AllEqualConstraint::new());
+        let domains = propagate(&or, &[vec![1], vec![1], vec![2], vec![1, 2, 3]]).unwrap();
+        assert_eq!(domains[3], vec![2]);
+
+        // the right side is violated, so the left one must not hold
+        let implies = ImpliesConstraint::new(AllDifferentConstraint::new(), 2, AllEqualConstraint::new());
+        let domains = propagate(&implies, &[vec![1], vec![0, 1, 2], vec![2], vec![3]]).unwrap();
+        assert_eq!(domains[1], vec![1]);
+
+        let not = NotConstraint::new(AllEqualConstraint::new());
+        let domains = propagate(&not, &[vec![1], vec![0, 1, 2]]).unwrap();
+        assert_eq!(domains[1], vec![0, 2]);
+    }
+
+    #[test]
+    fn arithmetic_matches_brute_force() {
+        let small: Vec<i32> = (-2..3).collect();
+        let wide: Vec<i32> = (-4..5).collect();
+        check_solutions(ProductConstraint::new, &[small.clone(), small.clone(), wide.clone()], |t| t[0] * t[1] == t[2]);
+        let divisors: Vec<i32> = vec![-2, -1, 1, 2];
+        check_solutions(DivisionConstraint::new, &[wide.clone(), divisors.clone(), wide.clone()], |t| t[0] / t[1] == t[2]);
+        check_solutions(ModuloConstraint::new, &[wide.clone(), divisors, wide.clone()], |t| t[0] % t[1] == t[2]);
+        check_solutions(AbsConstraint::new, &[wide, small.clone()], |t| t[0].abs() == t[1]);
+        check_solutions(MinConstraint::new, &vec![small.clone(); 4], |t| t[..3].iter().min() == Some(&t[3]));
+        check_solutions(MaxConstraint::new, &vec![small; 4], |t| t[..3].iter().max() == Some(&t[3]));
+    }
+
+    #[test]
+    fn a_zero_divisor_fails_the_branch() {
+        let domains = [(0..5).collect(), vec![0, 1, 2], vec![0, 1, 2]];
+        check_solutions(DivisionConstraint::new, &domains, |t| t[0].checked_div(t[1]) == Some(t[2]));
+        check_solutions(ModuloConstraint::new, &domains, |t| t[0].checked_rem(t[1]) == Some(t[2]));
+        assert_eq!(propagate(&DivisionConstraint::new(), &[vec![4], vec![0], (0..8).collect()]), None);
+
+        // forward checking drops 0 from the divisor before it is tried
+        let domains = propagate(&ModuloConstraint::new(), &[(0..5).collect(), vec![0, 1, 2], (0..3).collect()]).unwrap();
+        assert_eq!(domains[1], vec![1, 2]);
+    }
+
+    #[test]
+    fn arithmetic_bounds_reject_an_assigned_value_out_of_range() {
+        // x * y is at most 4, an assigned z of 7 can't be kept even though x and y are still open
+        assert_eq!(propagate(&ProductConstraint::new(), &[vec![1, 2], vec![1, 2], vec![7]]), None);
+        assert_eq!(propagate(&DivisionConstraint::new(), &[vec![1, 8], vec![2, 4], vec![5]]), None);
+
+        // x % y stays below 3 whatever x is, z is assigned before x and y
+        assert_eq!(propagate(&ModuloConstraint::new(), &[(0..6).collect(), vec![2, 3], vec![4]]), None);
+    }
+
+    #[test]
+    fn product_narrows_the_bounds() {
+        let domains = propagate(&ProductConstraint::new(), &[(1..5).collect(), (2..4).collect(), (-10..7).collect()]).unwrap();
+        assert_eq!(domains, vec![vec![1, 2, 3], vec![2, 3], (2..7).collect()]);
+    }
+
+    #[test]
+    fn counting_matches_brute_force() {
+        let domains = vec![vec![0, 1, 2], vec![0, 1, 2], vec![1, 2, 3], (-1..5).collect()];
+        check_solutions(|| CountConstraint::new(1), &domains,
+                        |t| t[..3].iter().filter(|&&x| x == 1).count() as i32 == t[3]);
+        check_solutions(|| AmongConstraint::new(HashSet::from_iter(vec![0, 3])), &domains,
+                        |t| t[..3].iter().filter(|&&x| x == 0 || x == 3).count() as i32 == t[3]);
+        check_solutions(NValueConstraint::new, &domains,
+                        |t| t[..3].iter().collect::<HashSet<_>>().len() as i32 == t[3]);
+    }
+
+    #[test]
+    fn counting_fixes_the_undecided_variables() {
+        // two of the three variables must be 1, and only two can be
+        let domains = propagate(&CountConstraint::new(1), &[vec![0, 1], vec![1, 2], vec![0, 2], vec![2]]).unwrap();
+        assert_eq!(domains, vec![vec![1], vec![1], vec![0, 2], vec![2]]);
+
+        // a single value is allowed, the one already taken
+        let domains = propagate(&NValueConstraint::new(), &[vec![2], vec![1, 2, 3], vec![2, 4], vec![1]]).unwrap();
+        assert_eq!(domains, vec![vec![2], vec![2], vec![2], vec![1]]);
+    }
+
+    #[test]
+    fn inverse_matches_brute_force() {
+        check_solutions(InverseConstraint::new, &vec![(-1..4).collect(); 6], |t| {
+            (0..3).all(|i| (0..3).contains(&t[i]) && t[3 + t[i] as usize] == i as i32)
+                && (0..3).all(|j| (0..3).contains(&t[3 + j]) && t[t[3 + j] as usize] == j as i32)
+        });
+    }
+
+    #[test]
+    fn inverse_filters_both_sides() {
+        let domains = propagate(&InverseConstraint::new(), &[vec![1], vec![0, 1, 2], vec![0, 1, 2], vec![1, 2], vec![0, 2], vec![0, 1]]).unwrap();
+        assert_eq!(domains, vec![vec![1], vec![2], vec![0], vec![2], vec![0], vec![1]]);
+    }
+
+    #[test]
+    fn channeling_matches_brute_force() {
+        check_solutions(|| ChannelingConstraint::new(2), &[vec![0, 1], vec![0, 1], vec![0, 1], (0..6).collect()],
+                        |t| (2..5).contains(&t[3]) && (0..3).all(|i| (t[i] == 1) == (t[3] == 2 + i as i32)));
+    }
+
+    #[test]
+    fn channeling_links_the_booleans_to_the_value() {
+        let domains = propagate(&ChannelingConstraint::new(1), &[vec![0], vec![0, 1], vec![0, 1], vec![1, 2]]).unwrap();
+        assert_eq!(domains, vec![vec![0], vec![1], vec![0], vec![2]]);
+    }
+
+    #[test]
+    fn bin_packing_matches_brute_force() {
+        let sizes = vec![3, 1, 2, 2];
+        let mut domains = vec![(-1..3).collect(); 4];
+        domains.extend(vec![(0..6).collect(); 2]);
+        check_solutions(|| BinPackingConstraint::new(sizes.clone()), &domains, |t| {
+            (0..4).all(|item| (0..2).contains(&t[item]))
+                && (0..2).all(|bin| (0..4).filter(|&item| t[item] == bin as i32).map(|item| sizes[item]).sum::<i32>() == t[4 + bin])
+        });
+    }
+
+    #[test]
+    fn bin_packing_sends_items_where_they_fit() {
+        // the first bin already holds 3 out of at most 4, the item of size 2 must go to the second one
+        let domains = propagate(&BinPackingConstraint::new(vec![3, 2, 1]), &[vec![0], vec![0, 1], vec![0, 1], (0..5).collect(), (0..5).collect()]).unwrap();
+        assert_eq!(domains, vec![vec![0], vec![1], vec![0, 1], vec![3, 4], vec![2, 3]]);
+    }
+
+    #[test]
+    fn sliding_sum_matches_brute_force() {
+        check_solutions(|| SlidingSumConstraint::new(3, 2, 4), &vec![(0..3).collect(); 5],
+                        |t| t.windows(3).all(|window| (2..=4).contains(&window.iter().sum::<i32>())));
+    }
+
+    #[test]
+    fn sequence_matches_brute_force() {
+        let set: HashSet<i32> = HashSet::from_iter(vec![1, 3]);
+        check_solutions(|| SequenceConstraint::new(set.clone(), 2, 1, 1), &vec![(0..4).collect(); 5],
+                        |t| t.windows(2).all(|window| window.iter().filter(|value| set.contains(value)).count() == 1));
+    }
+
+    #[test]
+    fn sliding_sum_reasons_over_overlapping_windows() {
+        // x1 + x2 >= 5 once x0 is 0, which leaves at most 1 for x3 in the next window
+        let domains = propagate(&SlidingSumConstraint::new(3, 5, 6), &[vec![0], (0..4).collect(), (0..4).collect(), (0..4).collect()]).unwrap();
+        assert_eq!(domains, vec![vec![0], vec![2, 3], vec![2, 3], vec![0, 1]]);
+
+        // the first window needs x2 in the set, and so does the second one for x3
+        let set = HashSet::from_iter(vec![1]);
+        let domains = propagate(&SequenceConstraint::new(set, 3, 2, 2), &[vec![1], vec![0], (0..3).collect(), (0..3).collect()]).unwrap();
+        assert_eq!(domains, vec![vec![1], vec![0], vec![1], vec![1]]);
+    }
+
+    #[test]
+    fn clauses_over_literals() {
+        let booleans = vec![vec![0, 1]; 4];
+        check_solutions(|| BoolOrConstraint::new(vec![true, false, true, false]), &booleans,
+                        |t| t[0] == 1 || t[1] == 0 || t[2] == 1 || t[3] == 0);
+        check_solutions(|| BoolAndConstraint::new(vec![true, false, true, false]), &booleans,
+                        |t| t == [1, 0, 1, 0]);
+        check_solutions(|| BoolOrConstraint::new(vec![false]), &booleans[..1], |t| t[0] == 0);
+    }
+
+    #[test]
+    fn clause_propagates_its_last_literal() {
+        let clause = BoolOrConstraint::new(vec![true, false, true]);
+        assert_eq!(propagate(&clause, &[vec![0], vec![0, 1], vec![0, 1]]).unwrap(), vec![vec![0], vec![0, 1], vec![0, 1]]);
+        assert_eq!(propagate(&clause, &[vec![0], vec![1], vec![0, 1]]).unwrap(), vec![vec![0], vec![1], vec![1]]);
+        assert_eq!(propagate(&clause, &[vec![0], vec![0, 1], vec![0]]).unwrap(), vec![vec![0], vec![0], vec![0]]);
+        assert!(propagate(&clause, &[vec![0], vec![1], vec![0]]).is_none());
+    }
+
+    #[test]
+    fn clause_watches_move_past_failed_literals() {
+        let clause = BoolOrConstraint::new(vec![true, false, true, false]);
+        let variables: Vec<VarId> = (0..4).map(VarId::new).collect();
+        let mut domains = Domains::new(vec![Domain::boolean(); 4]);
+        let mut assignments = Assignments::new();
+        let watches = clause.subscriptions(&variables);
+        assert_eq!(watches, vec![(variables[0], Event::Assigned), (variables[1], Event::Assigned)]);
+        assert_eq!(clause.resubscribe(&variables, &watches, &mut domains, &mut assignments), None);
+
+        assignments.insert(variables[0], 0);
+        let watches = clause.resubscribe(&variables, &watches, &mut domains, &mut assignments).unwrap();
+        assert_eq!(watches, vec![(variables[1], Event::Assigned), (variables[2], Event::Assigned)]);
+
+        // with a single literal left the failed watch stays
+        assignments.insert(variables[1], 1);
+        assignments.insert(variables[3], 1);
+        let watches = clause.resubscribe(&variables, &watches, &mut domains, &mut assignments).unwrap();
+        assert_eq!(watches, vec![(variables[2], Event::Assigned), (variables[1], Event::Assigned)]);
+    }
+
+    #[test]
+    fn set_constraints_over_masks() {
+        let masks: Vec<i32> = (0..8).collect();
+        let two_masks = vec![masks.clone(), masks.clone()];
+        let three_masks = vec![masks.clone(), masks.clone(), masks];
+        check_solutions(SubsetConstraint::new, &two_masks, |t| t[0] & !t[1] == 0);
+        check_solutions(UnionConstraint::new, &three_masks, |t| t[2] == t[0] | t[1]);
+        check_solutions(IntersectionConstraint::new, &three_masks, |t| t[2] == t[0] & t[1]);
+        check_solutions(DisjointConstraint::new, &three_masks, |t| t[0] & t[1] == 0 && t[0] & t[2] == 0 && t[1] & t[2] == 0);
+        check_solutions(CardinalityConstraint::new, &[(0..8).collect(), (-1..5).collect()], |t| t[0].count_ones() as i32 == t[1]);
+    }
+
+    #[test]
+    fn set_bounds_are_narrowed() {
+        let (a, b, c) = (VarId::new(0), VarId::new(1), VarId::new(2));
+        let mut domains = Domains::new(vec![Domain::set(&[0], &[0, 1, 2]).unwrap(), Domain::set(&[], &[1, 3]).unwrap(), Domain::set(&[2, 3], &[0, 1, 2, 3, 4]).unwrap()]);
+        let mut assignments = Assignments::new();
+        assert!(UnionConstraint::new().call(&[a, b, c], &mut domains, &mut assignments, true).unwrap());
+
+        // c needs 3 from b, a needs to give 2 to c, c gets 0 from a and cannot get 4
+        assert_eq!(domains.get(&a).unwrap().set_bounds(), Some((0b101, 0b111)));
+        assert_eq!(domains.get(&b).unwrap().set_bounds(), Some((0b1000, 0b1010)));
+        assert_eq!(domains.get(&c).unwrap().set_bounds(), Some((0b1101, 0b1111)));
+
+        let mut domains = Domains::new(vec![Domain::set(&[0], &[0, 1, 2]).unwrap(), Domain::new(&[0, 1, 2, 3])]);
+        assignments.insert(b, 1);
+        assert!(CardinalityConstraint::new().call(&[a, b], &mut domains, &mut assignments, true).unwrap());
+        assert_eq!(domains.get(&a).unwrap().values(), vec![0b1]);
+    }
+
+    #[test]
+    fn forward_check_prunes_the_only_unassigned_variable() {
+        let x = VarId::new(0);
+        let y = VarId::new(1);
+        let mut domains = Domains::new(vec![Domain::new(&[0, 1, 2, 3]), Domain::new(&[0, 1, 2, 3, 4])]);
+        let mut assignments = Assignments::new();
+        assignments.insert(x, 2);
+
+        let constraint = FunctionConstraint::new(|values: Vec<i32>| Ok(values[0] < values[1]), true);
+        assert!(constraint.call(&[x, y], &mut domains, &mut assignments, true).unwrap());
+
+        // the values of y are the ones tried, x keeps its assignment
+        assert_eq!(assignments.get(&x), Some(&2));
+        assert_eq!(assignments.get(&y), None);
+        assert_eq!(domains.get(&y).unwrap().values(), vec![3, 4]);
+        assert_eq!(domains.get(&x).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn named_function_reads_the_variables_it_names() {
+        let (x, y, z) = (VarId::new(0), VarId::new(1), VarId::new(2));
+        check_solutions(|| NamedFunctionConstraint::new(move |a: &Assignment| Ok(a[x] * a[y] == a[z] + 1), true),
+                        &vec![(0..4).collect(); 3],
+                        |t| t[0] * t[1] == t[2] + 1);
+
+        // checked on partial assignments too, unassigned variables being skipped
+        check_solutions(|| NamedFunctionConstraint::new(move |a: &Assignment| {
+                            Ok(a.get(x) != Some(1) && (a.is_bound(y) || a.get(z) != Some(0)))
+                        }, false),
+                        &vec![(0..3).collect(); 3],
+                        |t| t[0] != 1);
+
+        // an unbound variable leaves the function undecided
+        check_solutions(|| NamedFunctionConstraint::new(move |a: &Assignment| Ok(match (a.get(x), a.get(y), a.get(z)) {
+                            (Some(x), Some(y), Some(z)) => x + y < z,
+                            _ => true,
+                        }), false),
+                        &vec![(0..4).collect(); 3],
+                        |t| t[0] + t[1] < t[2]);
+    }
+
+    #[test]
+    fn named_function_errors_are_returned() {
+        let x = VarId::new(0);
+        let constraint = NamedFunctionConstraint::new(move |a: &Assignment| {
+            if a[x] < 0 { Err(ConstraintError::new("Negative value.")) } else { Ok(true) }
+        }, true);
+        let mut domains = Domains::new(vec![Domain::new(&[-1, 1])]);
+        let mut assignments = Assignments::new();
+        assert!(constraint.call(&[x], &mut domains, &mut assignments, false).unwrap());
+        assignments.insert(x, -1);
+        assert!(constraint.call(&[x], &mut domains, &mut assignments, false).is_err());
+    }
+
+    #[test]
+    fn named_function_prunes_the_last_variable() {
+        let (x, y) = (VarId::new(0), VarId::new(1));
+        let constraint = NamedFunctionConstraint::new(move |a: &Assignment| Ok(a[x] < a[y]), true);
+        let domains = propagate(&constraint, &[vec![2], (0..5).collect()]).unwrap();
+        assert_eq!(domains, vec![vec![2], vec![3, 4]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Variable is not bound.")]
+    fn named_function_indexing_an_unbound_variable_panics() {
+        let (x, y) = (VarId::new(0), VarId::new(1));
+        let constraint = NamedFunctionConstraint::new(move |a: &Assignment| Ok(a[x] < a[y]), false);
+        propagate(&constraint, &[vec![2], (0..5).collect()]);
+    }
+}
@@ -1,45 +1,882 @@
-#[derive(Debug)]
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::propagation::Event;
+use crate::trail::Trail;
+use crate::variable::VarId;
+use crate::view::View;
+
+// the values of a domain, other sets as a sparse set whose first `size` values are the ones left,
+// contiguous sets as a bitset over `[offset, offset + 64 * words.len())`, ranges too large to be
+// materialised as their bounds and the values removed in between, booleans as two bits for 0 and 1,
+// sets as the masks between the elements they must and may contain and the masks removed in between,
+// reals as an interval over the boxes of a grid
+#[derive(Debug, Clone)]
+enum Values {
+    Sparse { values: Vec<i32>, positions: HashMap<i32, usize>, size: usize },
+    Bits { offset: i32, words: Vec<u64>, size: usize },
+    Interval { min: i64, max: i64, holes: BTreeSet<i32> },
+    Bool(u8),
+    Set { lower: u32, upper: u32, holes: BTreeSet<i32> },
+}
+
+// how to undo a removal from a bitset, an interval, a boolean or a set, a sparse set only needs
+// its size back
+#[derive(Debug, Clone, Copy)]
+enum Change {
+    Hidden(i32),
+    Bounds(i64, i64),
+    SetBounds(u32, u32),
+}
+
+/// Elements a set variable may contain, each one is a bit of the masks the variable takes: sets
+/// are limited to the 31 elements `0..SET_ELEMENTS`.
+pub const SET_ELEMENTS: i32 = 31;
+
+/// Mask of a set of elements in `0..SET_ELEMENTS`, the value a set variable takes. None when an
+/// element is out of that range.
+pub fn set_mask(elements: &[i32]) -> Option<i32> {
+    elements.iter().try_fold(0, |mask, &element| {
+        if (0..SET_ELEMENTS).contains(&element) { Some(mask | 1 << element) } else { None }
+    })
+}
+
+/// Elements of the set a mask stands for, in ascending order.
+pub fn set_elements(mask: i32) -> Vec<i32> {
+    (0..SET_ELEMENTS).filter(|&element| mask & 1 << element != 0).collect()
+}
+
+// the `index`th subset of `free`, the bits of `index` spread over the bits of `free`
+fn deposit(mut index: u64, mut free: u32) -> u32 {
+    let mut mask = 0;
+    while free != 0 {
+        let bit = free & free.wrapping_neg();
+        if index & 1 != 0 {
+            mask |= bit;
+        }
+        index >>= 1;
+        free &= free - 1;
+    }
+    mask
+}
+
+/// The reals a value `value` of a real variable stands for, the box `[value, value + 1] * precision`.
+pub fn real_box(value: i32, precision: f64) -> (f64, f64) {
+    (value as f64 * precision, (value as f64 + 1.0) * precision)
+}
+
+// how far below or above an integer a real bound may fall from rounding errors
+const ROUNDING: f64 = 1e-9;
+
+fn saturate(value: f64) -> i32 {
+    value.max(i32::MIN as f64).min(i32::MAX as f64) as i32
+}
+
+/// Values a variable can still take. Values hidden by forward checking are kept aside and
+/// brought back by restoring a mark taken before they were hidden.
+#[derive(Debug, Clone)]
 pub struct Domain {
-    pub values: Vec<i32>,
-    pub hidden: Vec<i32>,
-    pub states: Vec<usize>
+    values: Values,
+    hidden: Vec<Change>,
+    // the width of the boxes a real domain ranges over
+    precision: Option<f64>,
 }
 
 impl Domain {
+    /// Builds a domain over `set`, a bitset when the values are distinct and contiguous, a sparse
+    /// set of the distinct values in the given order otherwise.
     pub fn new(set: &[i32]) -> Self {
+        let mut sorted = Vec::from(set);
+        sorted.sort_unstable();
+        sorted.dedup();
+        let contiguous = match (sorted.first(), sorted.last()) {
+            (Some(&min), Some(&max)) => sorted.len() == set.len() && (max as i64 - min as i64) as usize + 1 == set.len(),
+            _ => false,
+        };
+
+        let values = if contiguous {
+            let offset = sorted[0];
+            let mut words = vec![0u64; set.len().div_ceil(64)];
+            for &value in sorted.iter() {
+                let bit = (value as i64 - offset as i64) as usize;
+                words[bit / 64] |= 1 << (bit % 64);
+            }
+            Values::Bits { offset, words, size: set.len() }
+        } else {
+            let mut values: Vec<i32> = Vec::default();
+            let mut positions: HashMap<i32, usize> = HashMap::default();
+            for &value in set {
+                if let Entry::Vacant(entry) = positions.entry(value) {
+                    entry.insert(values.len());
+                    values.push(value);
+                }
+            }
+            Values::Sparse { size: values.len(), values, positions }
+        };
+
+        Domain::with_values(values)
+    }
+
+    /// Builds a domain over `[min, max]` keeping only its bounds, values removed strictly
+    /// between them are recorded as holes.
+    pub fn interval(min: i32, max: i32) -> Self {
+        Domain::with_values(Values::Interval { min: min as i64, max: max as i64, holes: BTreeSet::default() })
+    }
+
+    /// Builds the domain of a set variable containing every element of `lower` and only elements
+    /// of `upper`, its values are the masks of the sets. None when an element is not within
+    /// `0..SET_ELEMENTS`.
+    pub fn set(lower: &[i32], upper: &[i32]) -> Option<Self> {
+        let (lower, upper) = (set_mask(lower)?, set_mask(upper)?);
+        Some(Domain::with_values(Values::Set { lower: lower as u32, upper: upper as u32, holes: BTreeSet::default() }))
+    }
+
+    /// Builds the domain of a real variable over `[min, max]`, an interval over the boxes of width
+    /// `precision` covering it which `real_box` turns back into reals.
+    pub fn real(min: f64, max: f64, precision: f64) -> Self {
+        let low = saturate((min / precision).floor());
+        let high = saturate((max / precision).ceil() - 1.0).max(low);
+        Domain {
+            precision: Some(precision),
+            ..Domain::interval(low, high)
+        }
+    }
+
+    /// Builds the domain of a boolean, `0` for false and `1` for true.
+    pub fn boolean() -> Self {
+        Domain::with_values(Values::Bool(0b11))
+    }
+
+    fn with_values(values: Values) -> Self {
         Domain {
-            values: Vec::from(set),
+            values,
             hidden: Vec::default(),
-            states: Vec::default()
+            precision: None,
         }
     }
 
-    pub fn reset_state(&mut self) {
-        self.values.extend(self.hidden.iter());
-        self.hidden.clear();
-        self.states.clear()
+    /// Whether the domain is a bitset.
+    pub fn is_bitset(&self) -> bool {
+        matches!(self.values, Values::Bits { .. })
+    }
+
+    /// Whether the domain only keeps its bounds and holes, its values should then not be enumerated.
+    pub fn is_interval(&self) -> bool {
+        matches!(self.values, Values::Interval { .. })
+    }
+
+    pub fn is_boolean(&self) -> bool {
+        matches!(self.values, Values::Bool(_))
+    }
+
+    /// The width of the boxes of a real domain, None for the other domains.
+    pub fn precision(&self) -> Option<f64> {
+        self.precision
+    }
+
+    /// The reals the values left cover, the values themselves unless the domain is real. None for
+    /// an empty domain.
+    pub fn real_bounds(&self) -> Option<(f64, f64)> {
+        let (min, max) = (self.min()?, self.max()?);
+        match self.precision {
+            Some(precision) => Some((real_box(min, precision).0, real_box(max, precision).1)),
+            None => Some((min as f64, max as f64)),
+        }
+    }
+
+    /// Hides the values not covering some real of `[min, max]`. Boxes which only touch the range
+    /// are hidden too, the integers slightly out of it are kept in case of rounding errors.
+    pub fn set_real_bounds(&mut self, min: f64, max: f64) {
+        let (low, high) = match self.precision {
+            // a single real on the edge of two boxes keeps the upper one
+            Some(precision) => ((min / precision).floor(), ((max / precision).ceil() - 1.0).max((min / precision).floor())),
+            None => ((min - ROUNDING * min.abs().max(1.0)).ceil(), (max + ROUNDING * max.abs().max(1.0)).floor()),
+        };
+        self.set_min(saturate(low));
+        self.set_max(saturate(high));
+    }
+
+    /// Masks of the elements every set left contains and of the elements some set left contains,
+    /// the values of a domain which is not a set being taken as masks. None for an empty domain.
+    pub fn set_bounds(&self) -> Option<(i32, i32)> {
+        match &self.values {
+            Values::Set { lower, upper, holes } if holes.is_empty() => {
+                if lower & !upper == 0 { Some((*lower as i32, *upper as i32)) } else { None }
+            }
+            _ => self.iter().fold(None, |bounds, mask| match bounds {
+                Some((lower, upper)) => Some((lower & mask, upper | mask)),
+                None => Some((mask, mask)),
+            }),
+        }
     }
 
-    pub fn push_state(&mut self) {
-        self.states.push(self.values.len())
+    pub fn len(&self) -> usize {
+        match &self.values {
+            Values::Sparse { size, .. } => *size,
+            Values::Bits { size, .. } => *size,
+            Values::Interval { min, max, holes } if min <= max => {
+                (max - min + 1) as usize - holes.range(*min as i32..=*max as i32).count()
+            }
+            Values::Interval { .. } => 0,
+            Values::Bool(bits) => bits.count_ones() as usize,
+            Values::Set { lower, upper, .. } if lower & !upper != 0 => 0,
+            Values::Set { lower, upper, holes } => {
+                let within = |mask: u32| lower & !mask == 0 && mask & !upper == 0;
+                (1usize << (upper & !lower).count_ones()) - holes.iter().filter(|&&mask| within(mask as u32)).count()
+            }
+        }
     }
 
-    pub fn pop_state(&mut self) {
-        if let Some(size) = self.states.pop() {
-            let diff = size - self.values.len();
-            if diff > 0 {
-                let bound = self.hidden.len() - diff;
-                self.values.extend(self.hidden[bound..].iter().cloned());
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 
-                for i in bound..self.hidden.len() {
-                    self.hidden.remove(i);
+    pub fn contains(&self, value: i32) -> bool {
+        match &self.values {
+            Values::Sparse { positions, size, .. } => positions.get(&value).is_some_and(|position| position < size),
+            Values::Bits { offset, words, .. } => {
+                let bit = value as i64 - *offset as i64;
+                0 <= bit && (bit as usize) < 64 * words.len() && words[bit as usize / 64] & 1 << (bit % 64) != 0
+            }
+            Values::Interval { min, max, holes } => *min <= value as i64 && value as i64 <= *max && !holes.contains(&value),
+            Values::Bool(bits) => (value == 0 || value == 1) && bits & 1 << value != 0,
+            Values::Set { lower, upper, holes } => {
+                let mask = value as u32;
+                value >= 0 && lower & !mask == 0 && mask & !upper == 0 && !holes.contains(&value)
+            }
+        }
+    }
+
+    pub fn min(&self) -> Option<i32> {
+        match &self.values {
+            Values::Sparse { values, size, .. } => values[..*size].iter().min().cloned(),
+            Values::Bits { offset, words, .. } => words.iter()
+                .position(|&word| word != 0)
+                .map(|i| offset + (64 * i + words[i].trailing_zeros() as usize) as i32),
+            Values::Interval { min, max, .. } => if min <= max { Some(*min as i32) } else { None },
+            Values::Bool(bits) => if *bits == 0 { None } else { Some(bits.trailing_zeros() as i32) },
+            // the masks between the bounds are ordered like integers
+            Values::Set { lower, upper, holes } if holes.is_empty() => if lower & !upper == 0 { Some(*lower as i32) } else { None },
+            Values::Set { .. } => self.iter().next(),
+        }
+    }
+
+    pub fn max(&self) -> Option<i32> {
+        match &self.values {
+            Values::Sparse { values, size, .. } => values[..*size].iter().max().cloned(),
+            Values::Bits { offset, words, .. } => words.iter()
+                .rposition(|&word| word != 0)
+                .map(|i| offset + (64 * i + 63 - words[i].leading_zeros() as usize) as i32),
+            Values::Interval { min, max, .. } => if min <= max { Some(*max as i32) } else { None },
+            Values::Bool(bits) => if *bits == 0 { None } else { Some(7 - bits.leading_zeros() as i32) },
+            Values::Set { lower, upper, holes } if holes.is_empty() => if lower & !upper == 0 { Some(*upper as i32) } else { None },
+            Values::Set { .. } => self.iter().last(),
+        }
+    }
+
+    /// Whether some value of the domain lies in `[min, max]`.
+    pub fn intersects(&self, min: i32, max: i32) -> bool {
+        match &self.values {
+            Values::Interval { min: low, max: high, holes } => {
+                let (low, high) = ((min as i64).max(*low), (max as i64).min(*high));
+                low <= high && ((high - low + 1) as usize) > holes.range(low as i32..=high as i32).count()
+            }
+            _ => self.iter().any(|value| min <= value && value <= max),
+        }
+    }
+
+    /// The values left, in ascending order unless the domain is a sparse set.
+    pub fn iter(&self) -> impl Iterator<Item = i32> + '_ {
+        let (list, bits, interval, boolean, set) = match &self.values {
+            Values::Sparse { values, size, .. } => (Some(values[..*size].iter().cloned()), None, None, None, None),
+            Values::Bits { offset, words, .. } => (None, Some(words.iter().enumerate().flat_map(move |(i, &word)| {
+                (0..64).filter(move |bit| word & 1 << bit != 0).map(move |bit| offset + (64 * i + bit) as i32)
+            })), None, None, None),
+            Values::Interval { min, max, holes } => (None, None, Some((*min..=*max)
+                .map(|value| value as i32)
+                .filter(move |value| !holes.contains(value))), None, None),
+            Values::Bool(bits) => (None, None, None, Some((0..2).filter(move |value| bits & 1 << value != 0)), None),
+            Values::Set { lower, upper, holes } => {
+                let free = upper & !lower;
+                let count = if lower & !upper == 0 { 1u64 << free.count_ones() } else { 0 };
+                (None, None, None, None, Some((0..count)
+                    .map(move |index| (lower | deposit(index, free)) as i32)
+                    .filter(move |mask| !holes.contains(mask))))
+            }
+        };
+        list.into_iter().flatten()
+            .chain(bits.into_iter().flatten())
+            .chain(interval.into_iter().flatten())
+            .chain(boolean.into_iter().flatten())
+            .chain(set.into_iter().flatten())
+    }
+
+    pub fn values(&self) -> Vec<i32> {
+        self.iter().collect()
+    }
+
+    /// Removes for good the values `keep` rejects, they are not brought back by `restore`. Meant
+    /// for preprocessing, before any mark is taken.
+    pub fn retain(&mut self, keep: impl Fn(i32) -> bool) {
+        let removed: Vec<i32> = self.iter().filter(|&value| !keep(value)).collect();
+        for value in removed {
+            self.remove(value);
+            if let Values::Sparse { values, positions, size } = &mut self.values {
+                // out of the hidden values too, keeping the order they are brought back in
+                values.remove(*size);
+                positions.remove(&value);
+                for (position, value) in values.iter().enumerate().skip(*size) {
+                    positions.insert(*value, position);
                 }
             }
         }
     }
 
+    pub fn reset_state(&mut self) {
+        if let Values::Sparse { values, size, .. } = &mut self.values {
+            *size = values.len();
+        }
+        while let Some(change) = self.hidden.pop() {
+            self.undo(change);
+        }
+    }
+
+    /// What `restore` needs to bring back the values hidden from now on, the size of a sparse
+    /// set or the number of changes made to the other domains.
+    pub fn mark(&self) -> usize {
+        match &self.values {
+            Values::Sparse { size, .. } => *size,
+            _ => self.hidden.len(),
+        }
+    }
+
+    pub fn restore(&mut self, mark: usize) {
+        if let Values::Sparse { size, .. } = &mut self.values {
+            *size = mark;
+            return;
+        }
+        while self.hidden.len() > mark {
+            let change = self.hidden.pop().unwrap();
+            self.undo(change);
+        }
+    }
+
     pub fn hide_value(&mut self, value: i32) {
-        self.values.remove(self.values.iter().position(|x| *x == value).unwrap());
-        self.hidden.push(value)
+        if let Some(change) = self.remove(value) {
+            self.hidden.push(change)
+        }
+    }
+
+    /// Hides the values below `value`, only the bound moves for an interval.
+    pub fn set_min(&mut self, value: i32) {
+        if let Values::Interval { min, max, holes } = &mut self.values {
+            if (value as i64) > *min {
+                self.hidden.push(Change::Bounds(*min, *max));
+                *min = value as i64;
+                while *min <= *max && holes.contains(&(*min as i32)) {
+                    *min += 1;
+                }
+            }
+        } else {
+            let below: Vec<i32> = self.iter().filter(|&other| other < value).collect();
+            for other in below {
+                self.hide_value(other);
+            }
+        }
+    }
+
+    /// Hides the values above `value`, only the bound moves for an interval.
+    pub fn set_max(&mut self, value: i32) {
+        if let Values::Interval { min, max, holes } = &mut self.values {
+            if (value as i64) < *max {
+                self.hidden.push(Change::Bounds(*min, *max));
+                *max = value as i64;
+                while *min <= *max && holes.contains(&(*max as i32)) {
+                    *max -= 1;
+                }
+            }
+        } else {
+            let above: Vec<i32> = self.iter().filter(|&other| other > value).collect();
+            for other in above {
+                self.hide_value(other);
+            }
+        }
+    }
+
+    /// Keeps the sets containing the elements of `mask`, only the bounds move for a set domain.
+    pub fn include(&mut self, mask: i32) {
+        if let Values::Set { lower, upper, .. } = &mut self.values {
+            if mask as u32 & !*lower != 0 {
+                self.hidden.push(Change::SetBounds(*lower, *upper));
+                *lower |= mask as u32;
+            }
+        } else {
+            let rejected: Vec<i32> = self.iter().filter(|&value| value & mask != mask).collect();
+            for value in rejected {
+                self.hide_value(value);
+            }
+        }
     }
-}
\ No newline at end of file
+
+    /// Keeps the sets without the elements of `mask`, only the bounds move for a set domain.
+    pub fn exclude(&mut self, mask: i32) {
+        if let Values::Set { lower, upper, .. } = &mut self.values {
+            if mask as u32 & *upper != 0 {
+                self.hidden.push(Change::SetBounds(*lower, *upper));
+                *upper &= !(mask as u32);
+            }
+        } else {
+            let rejected: Vec<i32> = self.iter().filter(|&value| value & mask != 0).collect();
+            for value in rejected {
+                self.hide_value(value);
+            }
+        }
+    }
+
+    fn undo(&mut self, change: Change) {
+        match (&mut self.values, change) {
+            (Values::Bits { offset, words, size }, Change::Hidden(value)) => {
+                let bit = (value as i64 - *offset as i64) as usize;
+                words[bit / 64] |= 1 << (bit % 64);
+                *size += 1;
+            }
+            (Values::Interval { holes, .. }, Change::Hidden(value)) => {
+                holes.remove(&value);
+            }
+            (Values::Bool(bits), Change::Hidden(value)) => *bits |= 1 << value,
+            (Values::Set { holes, .. }, Change::Hidden(value)) => {
+                holes.remove(&value);
+            }
+            (Values::Set { lower, upper, .. }, Change::SetBounds(old_lower, old_upper)) => {
+                *lower = old_lower;
+                *upper = old_upper;
+            }
+            (Values::Interval { min, max, .. }, Change::Bounds(old_min, old_max)) => {
+                *min = old_min;
+                *max = old_max;
+            }
+            _ => unreachable!("Sparse sets are restored by their size and bounds only change on intervals and sets."),
+        }
+    }
+
+    // the value must be in the domain, returns how to bring it back unless the domain is a sparse
+    // set, whose removed values are swapped right after the ones left
+    fn remove(&mut self, value: i32) -> Option<Change> {
+        match &mut self.values {
+            Values::Sparse { values, positions, size } => {
+                let position = positions[&value];
+                assert!(position < *size, "Value is not in the domain.");
+                let last = *size - 1;
+                values.swap(position, last);
+                positions.insert(values[position], position);
+                positions.insert(value, last);
+                *size = last;
+                return None;
+            }
+            Values::Bits { offset, words, size } => {
+                let bit = (value as i64 - *offset as i64) as usize;
+                assert!(words[bit / 64] & 1 << (bit % 64) != 0, "Value is not in the domain.");
+                words[bit / 64] &= !(1 << (bit % 64));
+                *size -= 1;
+            }
+            Values::Bool(bits) => {
+                assert!((value == 0 || value == 1) && *bits & 1 << value != 0, "Value is not in the domain.");
+                *bits &= !(1 << value);
+            }
+            Values::Set { lower, upper, holes } => {
+                let mask = value as u32;
+                assert!(value >= 0 && *lower & !mask == 0 && mask & !*upper == 0 && holes.insert(value), "Value is not in the domain.");
+            }
+            Values::Interval { min, max, holes } => {
+                let (old_min, old_max) = (*min, *max);
+                assert!(old_min <= value as i64 && value as i64 <= old_max, "Value is not in the domain.");
+                if value as i64 == old_min {
+                    *min += 1;
+                    while *min <= *max && holes.contains(&(*min as i32)) {
+                        *min += 1;
+                    }
+                } else if value as i64 == old_max {
+                    *max -= 1;
+                    while *min <= *max && holes.contains(&(*max as i32)) {
+                        *max -= 1;
+                    }
+                } else {
+                    assert!(holes.insert(value), "Value is not in the domain.");
+                    return Some(Change::Hidden(value));
+                }
+                return Some(Change::Bounds(old_min, old_max));
+            }
+        }
+        Some(Change::Hidden(value))
+    }
+}
+
+
+/// Domains of the variables of a problem, indexed by their `VarId`, along with the trail
+/// bringing them and the propagators' state back on backtracking.
+#[derive(Debug, Clone, Default)]
+pub struct Domains {
+    domains: Vec<Domain>,
+    trail: Trail,
+    // size and bounds of the domains handed out by `get_mut` since the events were last taken
+    changed: Vec<(VarId, usize, Option<i32>, Option<i32>)>,
+    watched: Vec<bool>,
+    // domains of the views constraints are called with, their handles come past the variables
+    views: Vec<(View, Domain)>,
+}
+
+impl Domains {
+    pub fn new(domains: Vec<Domain>) -> Self {
+        Domains {
+            watched: vec![false; domains.len()],
+            domains,
+            trail: Trail::new(),
+            changed: Vec::default(),
+            views: Vec::default(),
+        }
+    }
+
+    pub fn get(&self, variable: &VarId) -> Option<&Domain> {
+        match variable.index().checked_sub(self.domains.len()) {
+            Some(view) => self.views.get(view).map(|(_, domain)| domain),
+            None => self.domains.get(variable.index()),
+        }
+    }
+
+    /// The domain is saved on the trail first, changes made through it are undone by `restore`.
+    pub fn get_mut(&mut self, variable: &VarId) -> Option<&mut Domain> {
+        if let Some(view) = variable.index().checked_sub(self.domains.len()) {
+            let (_, domain) = self.views.get_mut(view)?;
+            self.trail.save_domain(*variable, domain.mark());
+            return Some(domain);
+        }
+        self.save(*variable);
+        self.domains.get_mut(variable.index())
+    }
+
+    // saves the domain of a variable on the trail and looks out for its events, ahead of a change
+    fn save(&mut self, variable: VarId) {
+        let domain = match self.domains.get(variable.index()) {
+            Some(domain) => domain,
+            None => return,
+        };
+        self.trail.save_domain(variable, domain.mark());
+        if !self.watched[variable.index()] {
+            self.watched[variable.index()] = true;
+            self.changed.push((variable, domain.len(), domain.min(), domain.max()));
+        }
+    }
+
+    /// The handle of the domain kept for `view`, if any.
+    pub(crate) fn view(&self, view: &View) -> Option<VarId> {
+        self.views.iter().position(|(other, _)| other == view).map(|index| VarId::new(self.domains.len() + index))
+    }
+
+    /// Keeps `domain` for `view` from now on, changes made to it are trailed but not reported as
+    /// events. Added before the search, it is never brought back past the values it started with.
+    pub(crate) fn add_view(&mut self, view: View, domain: Domain) -> VarId {
+        self.views.push((view, domain));
+        VarId::new(self.domains.len() + self.views.len() - 1)
+    }
+
+    /// The domain of a view, saved as by `get_mut`, along with the one of its variable.
+    pub(crate) fn view_mut(&mut self, handle: &VarId) -> Option<(&mut Domain, &Domain)> {
+        let (view, domain) = self.views.get_mut(handle.index().checked_sub(self.domains.len())?)?;
+        self.trail.save_domain(*handle, domain.mark());
+        Some((domain, self.domains.get(view.variable().index())?))
+    }
+
+    /// The domain of a view along with the one of its variable, saved as by `get_mut`.
+    pub(crate) fn variable_mut(&mut self, handle: &VarId) -> Option<(&Domain, &mut Domain)> {
+        let (view, _) = self.views.get(handle.index().checked_sub(self.domains.len())?)?;
+        let variable = view.variable();
+        self.save(variable);
+        let (_, domain) = &self.views[handle.index() - self.domains.len()];
+        Some((domain, self.domains.get_mut(variable.index())?))
+    }
+
+    /// The strongest event each domain went through since the events were last taken.
+    pub fn take_events(&mut self) -> Vec<(VarId, Event)> {
+        let mut events: Vec<(VarId, Event)> = Vec::default();
+        for (variable, len, min, max) in self.changed.drain(..) {
+            self.watched[variable.index()] = false;
+            let domain = &self.domains[variable.index()];
+            if domain.len() == len {
+                continue;
+            }
+
+            let event = if domain.len() == 1 {
+                Event::Assigned
+            } else if (domain.min(), domain.max()) != (min, max) {
+                Event::Bounds
+            } else {
+                Event::Removed
+            };
+            events.push((variable, event));
+        }
+        events
+    }
+
+    pub fn trail(&self) -> &Trail {
+        &self.trail
+    }
+
+    pub fn trail_mut(&mut self) -> &mut Trail {
+        &mut self.trail
+    }
+
+    pub fn checkpoint(&mut self) {
+        self.trail.checkpoint()
+    }
+
+    /// Brings the domains and the trail back to the last checkpoint, the events of the changes
+    /// undone are dropped.
+    pub fn restore(&mut self) {
+        let (domains, views) = (&mut self.domains, &mut self.views);
+        self.trail.restore(|variable, mark| match variable.index().checked_sub(domains.len()) {
+            Some(view) => views[view].1.restore(mark),
+            None => domains[variable.index()].restore(mark),
+        });
+        for (variable, ..) in self.changed.drain(..) {
+            self.watched[variable.index()] = false;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.domains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Domain> {
+        self.domains.iter()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::{real_box, set_elements, set_mask, Domain, Domains};
+    use crate::propagation::Event;
+    use crate::variable::VarId;
+
+    #[test]
+    fn contiguous_values_make_a_bitset() {
+        assert!(Domain::new(&[3, 1, 2]).is_bitset());
+        assert!(Domain::new(&(-70..70).collect::<Vec<i32>>()).is_bitset());
+        assert!(!Domain::new(&[1, 3]).is_bitset());
+        assert!(!Domain::new(&[1, 1, 2]).is_bitset());
+        assert!(!Domain::new(&[]).is_bitset());
+        assert!(Domain::boolean().is_boolean() && !Domain::new(&[0, 1]).is_boolean());
+        assert_eq!(Domain::boolean().values(), vec![0, 1]);
+    }
+
+    #[test]
+    fn bitset_hides_and_restores_values() {
+        let mut domain = Domain::new(&(-70..70).collect::<Vec<i32>>());
+        assert_eq!((domain.len(), domain.min(), domain.max()), (140, Some(-70), Some(69)));
+
+        let outer = domain.mark();
+        for value in -70..65 {
+            domain.hide_value(value);
+        }
+        assert_eq!(domain.values(), vec![65, 66, 67, 68, 69]);
+        assert!(domain.contains(65) && !domain.contains(64) && !domain.contains(70));
+
+        let inner = domain.mark();
+        domain.hide_value(69);
+        domain.hide_value(65);
+        assert_eq!((domain.len(), domain.min(), domain.max()), (3, Some(66), Some(68)));
+
+        domain.restore(inner);
+        assert_eq!(domain.values(), vec![65, 66, 67, 68, 69]);
+        domain.restore(outer);
+        assert_eq!(domain.values(), (-70..70).collect::<Vec<i32>>());
+
+        domain.retain(|value| value % 2 == 0);
+        domain.hide_value(0);
+        domain.reset_state();
+        assert_eq!(domain.values(), (-35..35).map(|value| 2 * value).collect::<Vec<i32>>());
+    }
+
+    #[test]
+    fn interval_only_moves_its_bounds() {
+        let mut domain = Domain::interval(0, 1_000_000);
+        assert!(domain.is_interval());
+        assert_eq!((domain.len(), domain.min(), domain.max()), (1_000_001, Some(0), Some(1_000_000)));
+
+        let outer = domain.mark();
+        domain.set_min(10);
+        domain.hide_value(12);
+        domain.hide_value(11);
+        domain.set_max(20);
+        domain.hide_value(20);
+        assert_eq!(domain.values(), vec![10, 13, 14, 15, 16, 17, 18, 19]);
+        assert!(domain.intersects(11, 13) && !domain.intersects(11, 12) && !domain.intersects(20, 30));
+
+        let inner = domain.mark();
+        domain.hide_value(10);
+        assert_eq!(domain.min(), Some(13));
+        domain.set_max(12);
+        assert!(domain.is_empty());
+        assert_eq!((domain.min(), domain.max()), (None, None));
+
+        domain.restore(inner);
+        assert_eq!((domain.len(), domain.min(), domain.max()), (8, Some(10), Some(19)));
+        domain.restore(outer);
+        assert_eq!((domain.len(), domain.min(), domain.max()), (1_000_001, Some(0), Some(1_000_000)));
+        assert!(domain.contains(12));
+    }
+
+    #[test]
+    fn sparse_set_swaps_out_the_values_it_hides() {
+        let mut domain = Domain::new(&[5, -2, 9, -2]);
+        assert!(!domain.is_bitset() && !domain.is_interval());
+        assert_eq!((domain.len(), domain.min(), domain.max()), (3, Some(-2), Some(9)));
+
+        let outer = domain.mark();
+        domain.hide_value(5);
+        assert_eq!(domain.values(), vec![9, -2]);
+        let inner = domain.mark();
+        domain.hide_value(9);
+        domain.hide_value(-2);
+        assert!(domain.is_empty() && !domain.contains(9));
+
+        domain.restore(inner);
+        assert_eq!(domain.values(), vec![-2, 9]);
+        domain.restore(outer);
+        assert_eq!(domain.len(), 3);
+        assert!(domain.contains(5));
+    }
+
+    #[test]
+    fn set_domain_ranges_over_the_masks_between_its_bounds() {
+        let mut domain = Domain::set(&[1], &[1, 2, 4]).unwrap();
+        assert_eq!(domain.values(), vec![0b10, 0b110, 0b10010, 0b10110]);
+        assert_eq!(set_elements(set_mask(&[4, 1]).unwrap()), vec![1, 4]);
+        assert_eq!(set_mask(&[30]), Some(1 << 30));
+        assert!(set_mask(&[31]).is_none() && set_mask(&[-1]).is_none());
+        assert!(Domain::set(&[], &[2, 31]).is_none());
+
+        let mark = domain.mark();
+        domain.include(set_mask(&[2]).unwrap());
+        domain.exclude(set_mask(&[4, 5]).unwrap());
+        assert_eq!((domain.len(), domain.set_bounds()), (1, Some((0b110, 0b110))));
+        domain.restore(mark);
+        domain.hide_value(0b10);
+        assert_eq!((domain.len(), domain.set_bounds()), (3, Some((0b10, 0b10110))));
+    }
+
+    #[test]
+    fn real_domain_ranges_over_the_boxes_covering_it() {
+        let mut domain = Domain::real(-1.0, 2.0, 0.5);
+        assert_eq!((domain.values(), domain.real_bounds()), ((-2..4).collect(), Some((-1.0, 2.0))));
+        assert_eq!(real_box(1, 0.5), (0.5, 1.0));
+
+        // the box ending at 0.0 and the one starting at 1.0 only touch the range
+        domain.set_real_bounds(0.0, 1.0);
+        assert_eq!(domain.values(), vec![0, 1]);
+        domain.set_real_bounds(0.5, 0.5);
+        assert_eq!(domain.values(), vec![1]);
+
+        let mut integers = Domain::new(&[0, 1, 2, 3, 4]);
+        integers.set_real_bounds(0.999_999_999_99, 3.5);
+        assert_eq!((integers.values(), integers.precision()), (vec![1, 2, 3], None));
+    }
+
+    #[test]
+    fn changed_domains_report_their_strongest_event() {
+        let (x, y, z, w) = (VarId::new(0), VarId::new(1), VarId::new(2), VarId::new(3));
+        let mut domains = Domains::new(vec![Domain::new(&[1, 2, 3]); 4]);
+        domains.get_mut(&x).unwrap().hide_value(2);
+        domains.get_mut(&y).unwrap().set_max(2);
+        domains.get_mut(&z).unwrap().set_min(3);
+        domains.get_mut(&w).unwrap();
+        assert_eq!(domains.take_events(), vec![(x, Event::Removed), (y, Event::Bounds), (z, Event::Assigned)]);
+        assert!(domains.take_events().is_empty());
+
+        domains.checkpoint();
+        domains.get_mut(&x).unwrap().hide_value(1);
+        domains.restore();
+        assert!(domains.take_events().is_empty());
+    }
+
+    // xorshift, enough to drive the operations of the property tests
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+    }
+
+    // random operations on `domain` through the trail must leave the same values as on a set saved
+    // whole at each checkpoint
+    fn check_against_model(mut domain: Domain, seed: u64) {
+        let variable = VarId::new(0);
+        let mut random = Random(seed);
+        let mut model: BTreeSet<i32> = domain.iter().collect();
+        let (low, high) = (domain.min().unwrap() - 2, domain.max().unwrap() + 2);
+
+        let removed: Vec<i32> = model.iter().cloned().filter(|_| random.below(8) == 0).collect();
+        domain.retain(|value| !removed.contains(&value));
+        model.retain(|value| !removed.contains(value));
+
+        let mut domains = Domains::new(vec![domain]);
+        let mut checkpoints: Vec<BTreeSet<i32>> = Vec::default();
+        for _ in 0..400 {
+            let value = low + random.below((high - low + 1) as usize) as i32;
+            match random.below(10) {
+                0 | 1 => {
+                    domains.checkpoint();
+                    checkpoints.push(model.clone());
+                }
+                2 | 3 => {
+                    domains.restore();
+                    if let Some(checkpoint) = checkpoints.pop() {
+                        model = checkpoint;
+                    }
+                }
+                4 => {
+                    domains.get_mut(&variable).unwrap().set_min(value);
+                    model.retain(|&other| other >= value);
+                }
+                5 => {
+                    domains.get_mut(&variable).unwrap().set_max(value);
+                    model.retain(|&other| other <= value);
+                }
+                _ => if model.contains(&value) {
+                    domains.get_mut(&variable).unwrap().hide_value(value);
+                    model.remove(&value);
+                }
+            }
+
+            let domain = domains.get(&variable).unwrap();
+            let mut values = domain.values();
+            values.sort_unstable();
+            assert_eq!(values, model.iter().cloned().collect::<Vec<i32>>());
+            assert_eq!(domain.len(), model.len());
+            assert_eq!((domain.min(), domain.max()), (model.iter().next().cloned(), model.iter().next_back().cloned()));
+            assert_eq!(domain.contains(value), model.contains(&value));
+        }
+    }
+
+    #[test]
+    fn domains_behave_like_their_model_across_checkpoints() {
+        for seed in 1..50 {
+            let sparse: Vec<i32> = (0..40).map(|value| 3 * value - 50).collect();
+            check_against_model(Domain::new(&sparse), seed);
+            check_against_model(Domain::new(&(-30..100).collect::<Vec<i32>>()), seed);
+            check_against_model(Domain::interval(-30, 100), seed);
+            check_against_model(Domain::boolean(), seed);
+            check_against_model(Domain::set(&[1], &[0, 1, 3, 4, 6]).unwrap(), seed);
+        }
+    }
+}
@@ -0,0 +1,555 @@
+use std::convert::TryFrom;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+
+use crate::constraint::{AbsConstraint, Constraint, ConstraintError, DivisionConstraint, FunctionConstraint,
+                        LinearConstraint, MaxConstraint, MinConstraint, ModuloConstraint, ProductConstraint, Relation};
+use crate::domain::Domains;
+use crate::propagation::{Event, Priority};
+use crate::variable::{Assignments, VarId};
+
+
+#[derive(Debug, Clone)]
+pub enum Expression {
+    Constant(i32),
+    Variable(VarId),
+    Sum(Box<Expression>, Box<Expression>),
+    Difference(Box<Expression>, Box<Expression>),
+    Product(Box<Expression>, Box<Expression>),
+    Quotient(Box<Expression>, Box<Expression>),
+    Remainder(Box<Expression>, Box<Expression>),
+    Negation(Box<Expression>),
+    Abs(Box<Expression>),
+    Min(Vec<Expression>),
+    Max(Vec<Expression>),
+}
+
+impl From<i32> for Expression {
+    fn from(value: i32) -> Self {
+        Expression::Constant(value)
+    }
+}
+
+impl From<VarId> for Expression {
+    fn from(variable: VarId) -> Self {
+        Expression::Variable(variable)
+    }
+}
+
+pub fn abs(expression: impl Into<Expression>) -> Expression {
+    Expression::Abs(Box::new(expression.into()))
+}
+
+pub fn min(expressions: Vec<Expression>) -> Expression {
+    Expression::Min(expressions)
+}
+
+pub fn max(expressions: Vec<Expression>) -> Expression {
+    Expression::Max(expressions)
+}
+
+macro_rules! binary_operator {
+    ($operator:ident, $method:ident, $variant:ident) => {
+        impl<T: Into<Expression>> $operator<T> for Expression {
+            type Output = Expression;
+
+            fn $method(self, other: T) -> Expression {
+                Expression::$variant(Box::new(self), Box::new(other.into()))
+            }
+        }
+
+        impl<T: Into<Expression>> $operator<T> for VarId {
+            type Output = Expression;
+
+            fn $method(self, other: T) -> Expression {
+                Expression::$variant(Box::new(self.into()), Box::new(other.into()))
+            }
+        }
+
+        impl $operator<Expression> for i32 {
+            type Output = Expression;
+
+            fn $method(self, other: Expression) -> Expression {
+                Expression::$variant(Box::new(self.into()), Box::new(other))
+            }
+        }
+
+        impl $operator<VarId> for i32 {
+            type Output = Expression;
+
+            fn $method(self, other: VarId) -> Expression {
+                Expression::$variant(Box::new(self.into()), Box::new(other.into()))
+            }
+        }
+    };
+}
+
+binary_operator!(Add, add, Sum);
+binary_operator!(Sub, sub, Difference);
+binary_operator!(Mul, mul, Product);
+binary_operator!(Div, div, Quotient);
+binary_operator!(Rem, rem, Remainder);
+
+impl Neg for Expression {
+    type Output = Expression;
+
+    fn neg(self) -> Expression {
+        Expression::Negation(Box::new(self))
+    }
+}
+
+impl Neg for VarId {
+    type Output = Expression;
+
+    fn neg(self) -> Expression {
+        Expression::Negation(Box::new(self.into()))
+    }
+}
+
+impl Expression {
+    pub fn less_equal(self, other: impl Into<Expression>) -> Comparison {
+        Comparison::new(self, Relation::LessEqual, other.into(), 0)
+    }
+
+    pub fn less(self, other: impl Into<Expression>) -> Comparison {
+        Comparison::new(self, Relation::LessEqual, other.into(), -1)
+    }
+
+    pub fn greater_equal(self, other: impl Into<Expression>) -> Comparison {
+        Comparison::new(self, Relation::GreaterEqual, other.into(), 0)
+    }
+
+    pub fn greater(self, other: impl Into<Expression>) -> Comparison {
+        Comparison::new(self, Relation::GreaterEqual, other.into(), 1)
+    }
+
+    pub fn equal(self, other: impl Into<Expression>) -> Comparison {
+        Comparison::new(self, Relation::Equal, other.into(), 0)
+    }
+
+    pub fn not_equal(self, other: impl Into<Expression>) -> Comparison {
+        Comparison::new(self, Relation::NotEqual, other.into(), 0)
+    }
+
+    fn collect_variables(&self, variables: &mut Vec<VarId>) {
+        match self {
+            Expression::Constant(_) => {}
+            Expression::Variable(variable) => {
+                if !variables.contains(variable) {
+                    variables.push(*variable);
+                }
+            }
+            Expression::Sum(left, right) | Expression::Difference(left, right) | Expression::Product(left, right)
+            | Expression::Quotient(left, right) | Expression::Remainder(left, right) => {
+                left.collect_variables(variables);
+                right.collect_variables(variables);
+            }
+            Expression::Negation(expression) | Expression::Abs(expression) => expression.collect_variables(variables),
+            Expression::Min(expressions) | Expression::Max(expressions) => {
+                for expression in expressions {
+                    expression.collect_variables(variables);
+                }
+            }
+        }
+    }
+
+    // `sum(coefficient * variable) + constant` when the expression is linear, None as well when
+    // a coefficient or the constant overflows
+    pub(crate) fn linear(&self) -> Option<(Vec<(VarId, i64)>, i64)> {
+        let scale = |terms: Vec<(VarId, i64)>, factor: i64| -> Option<Vec<(VarId, i64)>> {
+            terms.into_iter().map(|(variable, coefficient)| Some((variable, coefficient.checked_mul(factor)?))).collect()
+        };
+        match self {
+            Expression::Constant(value) => Some((Vec::default(), *value as i64)),
+            Expression::Variable(variable) => Some((vec![(*variable, 1)], 0)),
+            Expression::Sum(left, right) => {
+                let (mut terms, left_constant) = left.linear()?;
+                let (right_terms, right_constant) = right.linear()?;
+                terms.extend(right_terms);
+                Some((terms, left_constant.checked_add(right_constant)?))
+            }
+            Expression::Difference(left, right) => {
+                let (mut terms, left_constant) = left.linear()?;
+                let (right_terms, right_constant) = right.linear()?;
+                terms.extend(scale(right_terms, -1)?);
+                Some((terms, left_constant.checked_sub(right_constant)?))
+            }
+            Expression::Negation(expression) => {
+                let (terms, constant) = expression.linear()?;
+                Some((scale(terms, -1)?, constant.checked_neg()?))
+            }
+            Expression::Product(left, right) => {
+                let (left_terms, left_constant) = left.linear()?;
+                let (right_terms, right_constant) = right.linear()?;
+                // only a scaling by a constant keeps the expression linear
+                let (terms, constant, factor) = match (left_terms.is_empty(), right_terms.is_empty()) {
+                    (_, true) => (left_terms, left_constant, right_constant),
+                    (true, false) => (right_terms, right_constant, left_constant),
+                    (false, false) => return None,
+                };
+                Some((scale(terms, factor)?, constant.checked_mul(factor)?))
+            }
+            _ => None,
+        }
+    }
+
+    // None when a divisor is 0, a min or max is over nothing or the value overflows, the
+    // comparison then does not hold
+    fn evaluate(&self, variables: &[VarId], values: &[i32]) -> Option<i64> {
+        let value_of = |expression: &Expression| expression.evaluate(variables, values);
+        Some(match self {
+            Expression::Constant(value) => *value as i64,
+            Expression::Variable(variable) => values[variables.iter().position(|v| v == variable).unwrap()] as i64,
+            Expression::Sum(left, right) => value_of(left)?.checked_add(value_of(right)?)?,
+            Expression::Difference(left, right) => value_of(left)?.checked_sub(value_of(right)?)?,
+            Expression::Product(left, right) => value_of(left)?.checked_mul(value_of(right)?)?,
+            Expression::Quotient(left, right) => value_of(left)?.checked_div(value_of(right)?)?,
+            Expression::Remainder(left, right) => value_of(left)?.checked_rem(value_of(right)?)?,
+            Expression::Negation(expression) => value_of(expression)?.checked_neg()?,
+            Expression::Abs(expression) => value_of(expression)?.checked_abs()?,
+            Expression::Min(expressions) => expressions.iter().map(value_of).collect::<Option<Vec<i64>>>()?.into_iter().min()?,
+            Expression::Max(expressions) => expressions.iter().map(value_of).collect::<Option<Vec<i64>>>()?.into_iter().max()?,
+        })
+    }
+
+    fn as_variable(&self) -> Option<VarId> {
+        match self {
+            Expression::Variable(variable) => Some(*variable),
+            _ => None,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub(crate) left: Expression,
+    pub(crate) relation: Relation,
+    pub(crate) right: Expression,
+    // strict comparisons are turned into large ones, `x < y` being `x <= y - 1`
+    pub(crate) offset: i64,
+}
+
+impl Comparison {
+    fn new(left: Expression, relation: Relation, right: Expression, offset: i64) -> Self {
+        Comparison {
+            left,
+            relation,
+            right,
+            offset,
+        }
+    }
+
+    pub fn compile(self) -> (ExpressionConstraint, Vec<VarId>) {
+        if let Some(compiled) = self.compile_linear() {
+            return compiled;
+        }
+        if let Some(compiled) = self.compile_arithmetic() {
+            return compiled;
+        }
+
+        let mut variables: Vec<VarId> = Vec::default();
+        self.left.collect_variables(&mut variables);
+        self.right.collect_variables(&mut variables);
+
+        let relation = self.relation;
+        let offset = self.offset;
+        let (left, right) = (self.left, self.right);
+        let positions = variables.clone();
+        let function = move |values: Vec<i32>| {
+            let (left, right) = match (left.evaluate(&positions, &values), right.evaluate(&positions, &values)) {
+                (Some(left), Some(right)) => (left, right + offset),
+                _ => return Ok(false),
+            };
+            Ok(match relation {
+                Relation::LessEqual => left <= right,
+                Relation::Equal => left == right,
+                Relation::GreaterEqual => left >= right,
+                Relation::NotEqual => left != right,
+            })
+        };
+        // the expression is only evaluated once every variable is assigned
+        (ExpressionConstraint::Function(FunctionConstraint::new(Box::new(function), true)), variables)
+    }
+
+    fn compile_linear(&self) -> Option<(ExpressionConstraint, Vec<VarId>)> {
+        let (left_terms, left_constant) = self.left.linear()?;
+        let (right_terms, right_constant) = self.right.linear()?;
+
+        // left - right <relation> offset
+        let mut variables: Vec<VarId> = Vec::default();
+        let mut coefficients: Vec<i64> = Vec::default();
+        let terms = left_terms.into_iter()
+            .chain(right_terms.into_iter().map(|(variable, coefficient)| (variable, -coefficient)));
+        for (variable, coefficient) in terms {
+            match variables.iter().position(|&v| v == variable) {
+                Some(index) => coefficients[index] = coefficients[index].checked_add(coefficient)?,
+                None => {
+                    variables.push(variable);
+                    coefficients.push(coefficient);
+                }
+            }
+        }
+
+        // coefficients or a constant out of the i32 range are left to the other compilations
+        let constant = i32::try_from(right_constant.checked_sub(left_constant)?.checked_add(self.offset)?).ok()?;
+        let coefficients: Vec<i32> = coefficients.into_iter()
+            .map(|coefficient| i32::try_from(coefficient).ok())
+            .collect::<Option<_>>()?;
+        Some((ExpressionConstraint::Linear(LinearConstraint::new(coefficients, self.relation, constant)), variables))
+    }
+
+    // `z == x op y`, `z == |x|`, `z == min(xs)` and `z == max(xs)` over plain variables
+    fn compile_arithmetic(&self) -> Option<(ExpressionConstraint, Vec<VarId>)> {
+        if self.relation != Relation::Equal || self.offset != 0 {
+            return None;
+        }
+
+        let (result, expression) = match (self.left.as_variable(), self.right.as_variable()) {
+            (Some(variable), _) => (variable, &self.right),
+            (None, Some(variable)) => (variable, &self.left),
+            (None, None) => return None,
+        };
+
+        let operands = |expressions: &[&Expression]| -> Option<Vec<VarId>> {
+            let mut variables: Vec<VarId> = expressions.iter().map(|expression| expression.as_variable()).collect::<Option<_>>()?;
+            variables.push(result);
+            Some(variables)
+        };
+
+        Some(match expression {
+            Expression::Product(x, y) => (ExpressionConstraint::Product(ProductConstraint::new()), operands(&[x, y])?),
+            Expression::Quotient(x, y) => (ExpressionConstraint::Division(DivisionConstraint::new()), operands(&[x, y])?),
+            Expression::Remainder(x, y) => (ExpressionConstraint::Modulo(ModuloConstraint::new()), operands(&[x, y])?),
+            Expression::Abs(x) => (ExpressionConstraint::Abs(AbsConstraint::new()), operands(&[x])?),
+            Expression::Min(xs) => (ExpressionConstraint::Min(MinConstraint::new()), operands(&xs.iter().collect::<Vec<_>>())?),
+            Expression::Max(xs) => (ExpressionConstraint::Max(MaxConstraint::new()), operands(&xs.iter().collect::<Vec<_>>())?),
+            _ => return None,
+        })
+    }
+}
+
+
+type ExpressionFunction = Box<dyn Fn(Vec<i32>) -> Result<bool, ConstraintError>>;
+
+pub enum ExpressionConstraint {
+    Linear(LinearConstraint),
+    Product(ProductConstraint),
+    Division(DivisionConstraint),
+    Modulo(ModuloConstraint),
+    Abs(AbsConstraint),
+    Min(MinConstraint),
+    Max(MaxConstraint),
+    Function(FunctionConstraint<ExpressionFunction>),
+}
+
+impl ExpressionConstraint {
+    fn constraint(&self) -> &dyn Constraint {
+        match self {
+            ExpressionConstraint::Linear(constraint) => constraint,
+            ExpressionConstraint::Product(constraint) => constraint,
+            ExpressionConstraint::Division(constraint) => constraint,
+            ExpressionConstraint::Modulo(constraint) => constraint,
+            ExpressionConstraint::Abs(constraint) => constraint,
+            ExpressionConstraint::Min(constraint) => constraint,
+            ExpressionConstraint::Max(constraint) => constraint,
+            ExpressionConstraint::Function(constraint) => constraint,
+        }
+    }
+}
+
+impl Constraint for ExpressionConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        self.constraint().call(variables, domains, assignments, forward_check)
+    }
+
+    fn subscriptions(&self, variables: &[VarId]) -> Vec<(VarId, Event)> {
+        self.constraint().subscriptions(variables)
+    }
+
+    fn resubscribe(&self,
+                   variables: &[VarId],
+                   subscriptions: &[(VarId, Event)],
+                   domains: &mut Domains,
+                   assignments: &mut Assignments) -> Option<Vec<(VarId, Event)>> {
+        self.constraint().resubscribe(variables, subscriptions, domains, assignments)
+    }
+
+    fn priority(&self) -> Priority {
+        self.constraint().priority()
+    }
+
+    fn pre_process(&self,
+                   variables: &[VarId],
+                   domains: &mut Domains) -> bool
+    {
+        self.constraint().pre_process(variables, domains)
+    }
+}
+
+
+/// Compiles a comparison between expressions, `constraint!(x + 2 * y <= z)` gives back the
+/// constraint along with the variables it must be called with.
+///
+/// ```
+/// use rust_constraint::constraint;
+/// use rust_constraint::problem::Problem;
+///
+/// let mut problem = Problem::new();
+/// let x = problem.add_variable("x", &[0, 1, 2, 3]);
+/// let y = problem.add_variable("y", &[0, 1, 2, 3]);
+/// let (constraint, variables) = constraint!(x + 2 * y == 5);
+/// problem.add_constraint(constraint, &variables);
+/// assert_eq!(problem.get_solutions().unwrap().len(), 2);
+/// ```
+#[macro_export]
+macro_rules! constraint {
+    (@split [$($left:tt)+] <= $($right:tt)+) => {
+        $crate::expression::Expression::from($($left)+).less_equal($($right)+).compile()
+    };
+    (@split [$($left:tt)+] >= $($right:tt)+) => {
+        $crate::expression::Expression::from($($left)+).greater_equal($($right)+).compile()
+    };
+    (@split [$($left:tt)+] == $($right:tt)+) => {
+        $crate::expression::Expression::from($($left)+).equal($($right)+).compile()
+    };
+    (@split [$($left:tt)+] != $($right:tt)+) => {
+        $crate::expression::Expression::from($($left)+).not_equal($($right)+).compile()
+    };
+    (@split [$($left:tt)+] < $($right:tt)+) => {
+        $crate::expression::Expression::from($($left)+).less($($right)+).compile()
+    };
+    (@split [$($left:tt)+] > $($right:tt)+) => {
+        $crate::expression::Expression::from($($left)+).greater($($right)+).compile()
+    };
+    (@split [$($left:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::constraint!(@split [$($left)* $next] $($rest)*)
+    };
+    ($($tokens:tt)+) => {
+        $crate::constraint!(@split [] $($tokens)+)
+    };
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::constraint::{Constraint, LinearConstraint, Relation};
+    use crate::problem::Problem;
+    use crate::variable::VarId;
+    use super::ExpressionConstraint;
+
+    // solutions of the compiled constraint over x, y and z, as (x, y, z) tuples
+    fn solve((constraint, variables): (ExpressionConstraint, Vec<VarId>), domains: &[Vec<i32>; 3]) -> Vec<Vec<i32>> {
+        let mut problem = Problem::new();
+        let xyz: Vec<VarId> = domains.iter().map(|values| problem.add_variable("v", values)).collect();
+        assert!(variables.iter().all(|variable| xyz.contains(variable)));
+        problem.add_constraint(constraint, &variables);
+
+        let mut solutions: Vec<Vec<i32>> = problem.get_solutions().unwrap().iter()
+            .map(|solution| xyz.iter().map(|variable| solution[variable]).collect())
+            .collect();
+        solutions.sort();
+        solutions
+    }
+
+    fn brute(domains: &[Vec<i32>; 3], check: impl Fn(i32, i32, i32) -> bool) -> Vec<Vec<i32>> {
+        let mut solutions: Vec<Vec<i32>> = Vec::default();
+        for &x in domains[0].iter() {
+            for &y in domains[1].iter() {
+                for &z in domains[2].iter() {
+                    if check(x, y, z) {
+                        solutions.push(vec![x, y, z]);
+                    }
+                }
+            }
+        }
+        solutions
+    }
+
+    fn variables() -> (VarId, VarId, VarId) {
+        (VarId::new(0), VarId::new(1), VarId::new(2))
+    }
+
+    #[test]
+    fn every_relation_compiles() {
+        let domains = [(-2..3).collect(), (-2..3).collect(), (-3..4).collect()];
+        let (x, y, z) = variables();
+        assert_eq!(solve(crate::constraint!(x + 2 * y <= z), &domains), brute(&domains, |x, y, z| x + 2 * y <= z));
+        assert_eq!(solve(crate::constraint!(x + 2 * y >= z), &domains), brute(&domains, |x, y, z| x + 2 * y >= z));
+        assert_eq!(solve(crate::constraint!(x + 2 * y == z), &domains), brute(&domains, |x, y, z| x + 2 * y == z));
+        assert_eq!(solve(crate::constraint!(x + 2 * y != z), &domains), brute(&domains, |x, y, z| x + 2 * y != z));
+        assert_eq!(solve(crate::constraint!(x + 2 * y < z), &domains), brute(&domains, |x, y, z| x + 2 * y < z));
+        assert_eq!(solve(crate::constraint!(x + 2 * y > z), &domains), brute(&domains, |x, y, z| x + 2 * y > z));
+    }
+
+    #[test]
+    fn each_shape_gets_its_own_constraint() {
+        let domains = [(-2..3).collect(), (-2..3).collect(), (-4..5).collect()];
+        let (x, y, z) = variables();
+
+        let linear = crate::constraint!(3 - (x - y) * 2 == -z);
+        assert!(matches!(linear.0, ExpressionConstraint::Linear(_)));
+        assert_eq!(solve(linear, &domains), brute(&domains, |x, y, z| 3 - (x - y) * 2 == -z));
+
+        let product = crate::constraint!(z == x * y);
+        assert!(matches!(product.0, ExpressionConstraint::Product(_)));
+        assert_eq!(solve(product, &domains), brute(&domains, |x, y, z| z == x * y));
+
+        let fallback = crate::constraint!(x * y + z > 1);
+        assert!(matches!(fallback.0, ExpressionConstraint::Function(_)));
+        assert_eq!(solve(fallback, &domains), brute(&domains, |x, y, z| x * y + z > 1));
+    }
+
+    #[test]
+    fn the_compiled_constraint_is_propagated_as_such() {
+        let (x, y, z) = variables();
+        let (constraint, variables) = crate::constraint!(x + 2 * y <= z);
+        let linear = LinearConstraint::new(vec![1, 2, -1], Relation::LessEqual, 0);
+        assert_eq!(constraint.subscriptions(&variables), linear.subscriptions(&variables));
+        assert_eq!(constraint.priority(), linear.priority());
+    }
+
+    #[test]
+    fn a_zero_divisor_fails_the_comparison() {
+        let domains = [(-2..3).collect(), (-2..3).collect(), (-2..3).collect()];
+        let (x, y, z) = variables();
+        let quotient = crate::constraint!(x * y / z >= 1);
+        assert!(matches!(quotient.0, ExpressionConstraint::Function(_)));
+        assert_eq!(solve(quotient, &domains), brute(&domains, |x, y, z| (x * y).checked_div(z).is_some_and(|q| q >= 1)));
+
+        let remainder = crate::constraint!(x * y % z == 0);
+        assert_eq!(solve(remainder, &domains), brute(&domains, |x, y, z| (x * y).checked_rem(z) == Some(0)));
+
+        // compiled to native constraints, which fail on a zero divisor as well
+        let quotient = crate::constraint!(z == x / y);
+        assert!(matches!(quotient.0, ExpressionConstraint::Division(_)));
+        assert_eq!(solve(quotient, &domains), brute(&domains, |x, y, z| x.checked_div(y) == Some(z)));
+        let remainder = crate::constraint!(z == x % y);
+        assert!(matches!(remainder.0, ExpressionConstraint::Modulo(_)));
+        assert_eq!(solve(remainder, &domains), brute(&domains, |x, y, z| x.checked_rem(y) == Some(z)));
+    }
+
+    #[test]
+    fn coefficients_out_of_range_are_not_truncated() {
+        let domains = [vec![0, 1], vec![-1, 0, 1], vec![0]];
+        let (x, y, z) = variables();
+
+        // 2^32 * x would be 0 * x once truncated to an i32
+        let large = crate::constraint!(x * 65536 * 65536 + y == z);
+        assert!(!matches!(large.0, ExpressionConstraint::Linear(_)));
+        assert_eq!(solve(large, &domains), vec![vec![0, 0, 0]]);
+
+        let shifted = crate::constraint!(x + y + 2147483647 + 2 >= z);
+        assert!(!matches!(shifted.0, ExpressionConstraint::Linear(_)));
+        assert_eq!(solve(shifted, &domains).len(), 6);
+
+        // 2^64 * x overflows even an i64, x == 1 then fails the comparison
+        let overflowing = crate::constraint!(x * 65536 * 65536 * 65536 * 65536 == y);
+        assert!(matches!(overflowing.0, ExpressionConstraint::Function(_)));
+        assert_eq!(solve(overflowing, &domains), vec![vec![0, 0, 0]]);
+    }
+}
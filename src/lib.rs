@@ -1,9 +1,23 @@
 #[allow(dead_code)]
-mod domain;
+pub mod domain;
 #[allow(dead_code)]
-mod variable;
+pub mod variable;
 #[allow(dead_code)]
-mod constraint;
+pub mod constraint;
+#[allow(dead_code)]
+pub mod automaton;
+#[allow(dead_code)]
+pub mod expression;
+#[allow(dead_code)]
+pub mod propagation;
+#[allow(dead_code)]
+pub mod problem;
+#[allow(dead_code)]
+pub mod real;
+#[allow(dead_code)]
+pub mod trail;
+#[allow(dead_code)]
+pub mod view;
 
 #[cfg(test)]
 mod tests {
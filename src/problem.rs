@@ -1,9 +1,445 @@
-use crate::constraint::Constraint;
+use crate::constraint::{Constraint, ConstraintError, ConstraintVariable, IntervalConstraint};
+use crate::domain::{Domain, Domains};
+use crate::propagation::{Event, PropagationQueue};
+use crate::variable::{Assignments, IntervalVar, VarId, Variable};
+use crate::view::{View, ViewConstraint};
+
+
+pub trait Solver {
+    fn get_solutions(&self,
+                     domains: &mut Domains,
+                     constraints: &[&ConstraintVariable],
+                     single: bool) -> Result<Vec<Assignments>, ConstraintError>;
+}
+
+
+#[derive(Debug)]
+pub struct BacktrackingSolver {
+    forward_check: bool,
+}
+
+impl BacktrackingSolver {
+    pub fn new(forward_check: bool) -> Self {
+        BacktrackingSolver {
+            forward_check
+        }
+    }
+
+    // runs the constraints woken up by `events`, the assignment or bisection just made, and by the
+    // values they hide, until none is left to run or one fails
+    fn propagate(&self,
+                 domains: &mut Domains,
+                 constraints: &[&ConstraintVariable],
+                 queue: &mut PropagationQueue,
+                 assignments: &mut Assignments,
+                 events: Vec<(VarId, Event)>) -> Result<bool, ConstraintError>
+    {
+        for (variable, event) in events {
+            queue.notify(variable, event, None);
+        }
+        while let Some(index) = queue.pop() {
+            let constraint = constraints[index];
+            if !constraint.constraint.call(&constraint.variables, domains, assignments, self.forward_check)? {
+                queue.clear();
+                return Ok(false);
+            }
+            if let Some(subscriptions) = constraint.constraint.resubscribe(&constraint.variables, queue.subscriptions(index), domains, assignments) {
+                queue.resubscribe(index, &subscriptions);
+            }
+
+            for (other, event) in domains.take_events() {
+                queue.notify(other, event, Some(index));
+            }
+        }
+        Ok(true)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn search(&self,
+              domains: &mut Domains,
+              constraints: &[&ConstraintVariable],
+              vconstraints: &[Vec<usize>],
+              queue: &mut PropagationQueue,
+              assignments: &mut Assignments,
+              solutions: &mut Vec<Assignments>,
+              single: bool) -> Result<(), ConstraintError>
+    {
+        // most constrained variable first, then the one with the smallest domain
+        let variable = (0..domains.len())
+            .map(VarId::new)
+            .filter(|variable| !assignments.contains_key(variable))
+            .min_by_key(|variable| (
+                std::cmp::Reverse(vconstraints[variable.index()].len()),
+                domains.get(variable).unwrap().len()));
+
+        let variable = match variable {
+            Some(variable) => variable,
+            None => {
+                solutions.push(assignments.clone());
+                return Ok(());
+            }
+        };
+
+        // a real variable is split in halves until a single box is left
+        let domain = domains.get(&variable).unwrap();
+        if let (Some(_), Some(min), Some(max)) = (domain.precision(), domain.min(), domain.max()) {
+            if min < max {
+                let middle = (min as i64 + (max as i64 - min as i64) / 2) as i32;
+                for &(low, high) in [(min, middle), (middle + 1, max)].iter() {
+                    domains.checkpoint();
+                    let half = domains.get_mut(&variable).unwrap();
+                    half.set_min(low);
+                    half.set_max(high);
+                    let events = domains.take_events();
+
+                    if !domains.get(&variable).unwrap().is_empty()
+                        && self.propagate(domains, constraints, queue, assignments, events)? {
+                        self.search(domains, constraints, vconstraints, queue, assignments, solutions, single)?;
+                    }
+
+                    domains.restore();
+
+                    if single && !solutions.is_empty() {
+                        break;
+                    }
+                }
+                return Ok(());
+            }
+        }
+
+        // the values of an interval are walked through rather than materialised
+        let values: Box<dyn Iterator<Item = i32>> = match (domain.is_interval(), domain.min(), domain.max()) {
+            (true, Some(min), Some(max)) => Box::new(min..=max),
+            _ => Box::new(domain.values().into_iter()),
+        };
+        for value in values {
+            if !domains.get(&variable).unwrap().contains(value) {
+                continue;
+            }
+            assignments.insert(variable, value);
+            domains.checkpoint();
+
+            if self.propagate(domains, constraints, queue, assignments, vec![(variable, Event::Assigned)])? {
+                self.search(domains, constraints, vconstraints, queue, assignments, solutions, single)?;
+            }
+
+            domains.restore();
+
+            if single && !solutions.is_empty() {
+                break;
+            }
+        }
+        assignments.remove(&variable);
+
+        Ok(())
+    }
+}
+
+impl Default for BacktrackingSolver {
+    fn default() -> Self {
+        BacktrackingSolver::new(true)
+    }
+}
+
+impl Solver for BacktrackingSolver {
+    fn get_solutions(&self,
+                     domains: &mut Domains,
+                     constraints: &[&ConstraintVariable],
+                     single: bool) -> Result<Vec<Assignments>, ConstraintError>
+    {
+        let mut vconstraints: Vec<Vec<usize>> = vec![Vec::default(); domains.len()];
+        let mut queue = PropagationQueue::new();
+        for (index, constraint) in constraints.iter().enumerate() {
+            for variable in constraint.variables.iter() {
+                vconstraints[variable.index()].push(index);
+            }
+            queue.add_propagator(constraint.constraint.priority(),
+                                 &constraint.constraint.subscriptions(&constraint.variables));
+            if let Some(subscriptions) = constraint.constraint.resubscribe(&constraint.variables, queue.subscriptions(index), domains, &mut Assignments::new()) {
+                queue.resubscribe(index, &subscriptions);
+            }
+        }
+
+        // the values removed before the search are not news to anyone
+        domains.take_events();
+
+        let mut solutions: Vec<Assignments> = Vec::default();
+        let mut assignments = Assignments::new();
+        self.search(domains, constraints, &vconstraints, &mut queue, &mut assignments, &mut solutions, single)?;
+        Ok(solutions)
+    }
+}
 
-pub trait Solver {}
 
 pub struct Problem {
-    solver: dyn Solver,
-    constraint: Vec<dyn Constraint>
+    solver: Box<dyn Solver>,
+    variables: Vec<Variable>,
+    domains: Vec<Domain>,
+    constraints: Vec<ConstraintVariable>,
+    // handed out from the top of the handles down, past any variable
+    views: Vec<(Variable, View)>,
+}
+
+impl Problem {
+    pub fn new() -> Self {
+        Problem::with_solver(Box::new(BacktrackingSolver::default()))
+    }
+
+    pub fn with_solver(solver: Box<dyn Solver>) -> Self {
+        Problem {
+            solver,
+            variables: Vec::default(),
+            domains: Vec::default(),
+            constraints: Vec::default(),
+            views: Vec::default(),
+        }
+    }
+
+    /// Adds a variable ranging over `values`, the returned handle is how the variable is
+    /// referred to by constraints and solutions.
+    pub fn add_variable(&mut self, name: &'static str, values: &[i32]) -> VarId {
+        self.variables.push(Variable::new(name));
+        self.domains.push(Domain::new(values));
+        VarId::new(self.variables.len() - 1)
+    }
+
+    /// Adds a variable ranging over `[min, max]` whose domain only keeps its bounds and holes,
+    /// for ranges too large to be listed.
+    pub fn add_range_variable(&mut self, name: &'static str, min: i32, max: i32) -> VarId {
+        self.variables.push(Variable::new(name));
+        self.domains.push(Domain::interval(min, max));
+        VarId::new(self.variables.len() - 1)
+    }
+
+    /// Adds a boolean variable taking `0` for false and `1` for true.
+    pub fn add_bool_variable(&mut self, name: &'static str) -> VarId {
+        self.variables.push(Variable::new(name));
+        self.domains.push(Domain::boolean());
+        VarId::new(self.variables.len() - 1)
+    }
+
+    /// Adds a set variable containing every element of `lower` and only elements of `upper`. Its
+    /// values are masks, which `set_elements` turns back into elements, so sets are limited to the
+    /// 31 elements `0..SET_ELEMENTS` and an element out of that range is an error.
+    pub fn add_set_variable(&mut self, name: &'static str, lower: &[i32], upper: &[i32]) -> Result<VarId, ConstraintError> {
+        let domain = Domain::set(lower, upper)
+            .ok_or_else(|| ConstraintError::new("Set elements range over 0..31."))?;
+        self.variables.push(Variable::new(name));
+        self.domains.push(domain);
+        Ok(VarId::new(self.variables.len() - 1))
+    }
+
+    /// Adds a real variable over `[min, max]`, searched by bisection down to boxes of width
+    /// `precision`. Its values are the boxes, which `real_box` turns back into reals.
+    pub fn add_real_variable(&mut self, name: &'static str, min: f64, max: f64, precision: f64) -> VarId {
+        self.variables.push(Variable::new(name));
+        self.domains.push(Domain::real(min, max, precision));
+        VarId::new(self.variables.len() - 1)
+    }
+
+    /// Adds an interval of time starting and ending within `earliest..=latest`, lasting one of
+    /// `durations`. An `optional` interval may be left out of the schedule, its presence is then
+    /// `0` rather than `1`. Its variables are named `name.start`, `name.duration`, `name.end` and
+    /// `name.presence`.
+    pub fn add_interval_variable(&mut self,
+                                 name: &'static str,
+                                 (earliest, latest): (i32, i32),
+                                 durations: &[i32],
+                                 optional: bool) -> IntervalVar {
+        let mut add = |part: &str, domain: Domain| {
+            self.variables.push(Variable::new(format!("{}.{}", name, part)));
+            self.domains.push(domain);
+            VarId::new(self.variables.len() - 1)
+        };
+        let interval = IntervalVar {
+            start: add("start", Domain::interval(earliest, latest)),
+            duration: add("duration", Domain::new(durations)),
+            end: add("end", Domain::interval(earliest, latest)),
+            presence: add("presence", Domain::new(if optional { &[0, 1] } else { &[1] })),
+        };
+        let shortest = durations.iter().copied().min().unwrap_or(0);
+        self.add_constraint(IntervalConstraint::new(earliest, shortest), &IntervalVar::variables(&[interval]));
+        interval
+    }
+
+    /// Adds a view such as `View::try_from(x + 3)` or `View::equal(x, 5)`, which constraints take
+    /// like a variable, its domain following the one of its variable rather than being searched.
+    /// Solutions only hold the values of the variables, `value` gives the ones of the views.
+    pub fn add_view(&mut self, name: &'static str, view: View) -> Result<VarId, ConstraintError> {
+        if self.view(view.variable()).is_some() {
+            return Err(ConstraintError::new("View must be derived from a variable rather than from another view."));
+        }
+        self.views.push((Variable::new(name), view));
+        Ok(VarId::new(usize::MAX - (self.views.len() - 1)))
+    }
+
+    fn view(&self, variable: VarId) -> Option<&View> {
+        self.views.get(usize::MAX - variable.index()).map(|(_, view)| view)
+    }
+
+    pub fn add_constraint<C: Constraint + 'static>(&mut self, constraint: C, variables: &[VarId]) {
+        let views: Vec<Option<View>> = variables.iter().map(|&variable| self.view(variable).copied()).collect();
+        if views.iter().all(Option::is_none) {
+            self.constraints.push(ConstraintVariable::new(Box::new(constraint), variables));
+        } else {
+            let bases: Vec<VarId> = variables.iter().zip(views.iter())
+                .map(|(&variable, view)| view.map_or(variable, |view| view.variable()))
+                .collect();
+            self.constraints.push(ConstraintVariable::new(Box::new(ViewConstraint::new(constraint, views)), &bases));
+        }
+    }
+
+    pub fn variable(&self, variable: VarId) -> &Variable {
+        match self.views.get(usize::MAX - variable.index()) {
+            Some((view, _)) => view,
+            None => &self.variables[variable.index()],
+        }
+    }
+
+    /// The value of a variable or a view in `solution`.
+    pub fn value(&self, variable: VarId, solution: &Assignments) -> Option<i32> {
+        match self.view(variable) {
+            Some(view) => solution.get(&view.variable()).map(|&value| view.image(value)),
+            None => solution.get(&variable).copied(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.variables.clear();
+        self.domains.clear();
+        self.constraints.clear();
+        self.views.clear();
+    }
+
+    pub fn get_solution(&self) -> Result<Option<Assignments>, ConstraintError> {
+        Ok(self.solve(true)?.pop())
+    }
+
+    pub fn get_solutions(&self) -> Result<Vec<Assignments>, ConstraintError> {
+        self.solve(false)
+    }
+
+    fn solve(&self, single: bool) -> Result<Vec<Assignments>, ConstraintError> {
+        let mut domains = Domains::new(self.domains.clone());
+
+        let constraints: Vec<&ConstraintVariable> = self.constraints.iter()
+            .filter(|constraint| !constraint.constraint.pre_process(&constraint.variables, &mut domains))
+            .collect();
+
+        if domains.iter().any(|domain| domain.is_empty()) {
+            return Ok(Vec::default());
+        }
+
+        self.solver.get_solutions(&mut domains, &constraints, single)
+    }
+}
+
+impl Default for Problem {
+    fn default() -> Self {
+        Problem::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{BacktrackingSolver, Problem};
+    use crate::constraint::{AllDifferentConstraint, Constraint, ConstraintError, ExactSumConstraint};
+    use crate::domain::Domains;
+    use crate::propagation::Priority;
+    use crate::variable::{Assignments, VarId};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // writes down when it is called
+    struct Logged {
+        priority: Priority,
+        calls: Rc<RefCell<Vec<Priority>>>,
+    }
+
+    impl Constraint for Logged {
+        fn call(&self, _: &[VarId], _: &mut Domains, _: &mut Assignments, _: bool) -> Result<bool, ConstraintError> {
+            self.calls.borrow_mut().push(self.priority);
+            Ok(true)
+        }
+
+        fn priority(&self) -> Priority {
+            self.priority
+        }
+    }
+
+    #[test]
+    fn solutions_are_counted_with_and_without_forward_checking() {
+        for &forward_check in [true, false].iter() {
+            let mut problem = Problem::with_solver(Box::new(BacktrackingSolver::new(forward_check)));
+            let variables = vec![problem.add_variable("a", &[1, 2, 3]),
+                                 problem.add_variable("b", &[1, 2, 3]),
+                                 problem.add_variable("c", &[1, 2, 3])];
+            problem.add_constraint(AllDifferentConstraint::new(), &variables);
+            assert_eq!(problem.get_solutions().unwrap().len(), 6);
+
+            problem.add_constraint(ExactSumConstraint::new(4, None), &variables[..2]);
+            assert_eq!(problem.get_solutions().unwrap().len(), 2);
+        }
+    }
+
+    #[test]
+    fn get_solution_gives_a_solution_or_none() {
+        let mut problem = Problem::new();
+        let a = problem.add_variable("a", &[1, 2, 3]);
+        let b = problem.add_variable("b", &[2, 3, 4]);
+        problem.add_constraint(ExactSumConstraint::new(7, None), &[a, b]);
+
+        let solution = problem.get_solution().unwrap().unwrap();
+        assert_eq!(solution[&a] + solution[&b], 7);
+        assert_eq!(solution.len(), 2);
+
+        problem.add_constraint(AllDifferentConstraint::new(), &[a, b]);
+        problem.add_constraint(ExactSumConstraint::new(8, None), &[a, b]);
+        assert!(problem.get_solution().unwrap().is_none());
+        assert!(problem.get_solutions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn handles_look_their_variable_up() {
+        let mut problem = Problem::new();
+        let a = problem.add_variable("a", &[1]);
+        let b = problem.add_variable("b", &[0, 1]);
+        assert_ne!(a, b);
+        assert_eq!(problem.variable(a).name(), "a");
+        assert_eq!(problem.variable(b).name(), "b");
+
+        let solutions = problem.get_solutions().unwrap();
+        assert_eq!(solutions.len(), 2);
+        assert!(solutions.iter().all(|solution| solution[&a] == 1 && solution.contains_key(&b)));
+
+        problem.reset();
+        let c = problem.add_variable("c", &[5]);
+        assert_eq!(c, a);
+        assert_eq!(problem.variable(c).name(), "c");
+    }
+
+    #[test]
+    fn set_elements_out_of_range_are_an_error() {
+        let mut problem = Problem::new();
+        assert!(problem.add_set_variable("small", &[0], &[0, 30]).is_ok());
+        assert!(problem.add_set_variable("large", &[], &[0, 31]).is_err());
+        assert!(problem.add_set_variable("negative", &[-1], &[-1, 0]).is_err());
+        assert_eq!(problem.get_solutions().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn cheap_constraints_run_before_expensive_ones() {
+        let mut problem = Problem::new();
+        let variables = vec![problem.add_variable("a", &[1, 2]), problem.add_variable("b", &[1, 2])];
+        let calls = Rc::new(RefCell::new(Vec::default()));
+        problem.add_constraint(Logged { priority: Priority::Expensive, calls: calls.clone() }, &variables);
+        problem.add_constraint(Logged { priority: Priority::Cheap, calls: calls.clone() }, &variables);
+        assert_eq!(problem.get_solutions().unwrap().len(), 4);
 
-}
\ No newline at end of file
+        // each assignment wakes both up, the cheap one is run first although added last
+        let calls = calls.borrow();
+        assert_eq!(calls.len(), 12);
+        assert!(calls.chunks(2).all(|pair| pair == [Priority::Cheap, Priority::Expensive]));
+    }
+}
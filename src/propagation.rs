@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+
+use crate::variable::VarId;
+
+
+/// Change of a domain, each event implies the ones after it: an assigned variable had its
+/// bounds changed, and bounds only change when values are removed.
+///
+/// A propagator subscribed to an event is woken up by it and by the events implying it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Event {
+    Assigned,
+    Bounds,
+    Removed,
+}
+
+
+/// Propagators of a priority class only run once the cheaper classes have nothing left to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Cheap,
+    Expensive,
+}
+
+
+/// Propagators waiting to run, woken up by the events they subscribed to.
+#[derive(Debug, Clone, Default)]
+pub struct PropagationQueue {
+    // for each variable, the propagators subscribed to it with the weakest event waking them
+    subscriptions: Vec<Vec<(usize, Event)>>,
+    // for each propagator, the variables it is subscribed to
+    subscribed: Vec<Vec<(VarId, Event)>>,
+    priorities: Vec<Priority>,
+    queues: [VecDeque<usize>; 2],
+    queued: Vec<bool>,
+}
+
+impl PropagationQueue {
+    pub fn new() -> Self {
+        PropagationQueue::default()
+    }
+
+    /// Registers a propagator, its index is the number of propagators added before it.
+    pub fn add_propagator(&mut self, priority: Priority, subscriptions: &[(VarId, Event)]) -> usize {
+        let propagator = self.priorities.len();
+        self.subscribed.push(Vec::default());
+        self.priorities.push(priority);
+        self.queued.push(false);
+        self.resubscribe(propagator, subscriptions);
+        propagator
+    }
+
+    pub fn subscriptions(&self, propagator: usize) -> &[(VarId, Event)] {
+        &self.subscribed[propagator]
+    }
+
+    /// Replaces the subscriptions of a propagator, the events already notified still wake it up.
+    pub fn resubscribe(&mut self, propagator: usize, subscriptions: &[(VarId, Event)]) {
+        for (variable, _) in std::mem::take(&mut self.subscribed[propagator]) {
+            self.subscriptions[variable.index()].retain(|&(other, _)| other != propagator);
+        }
+        for &(variable, event) in subscriptions {
+            if self.subscriptions.len() <= variable.index() {
+                self.subscriptions.resize(variable.index() + 1, Vec::default());
+            }
+            self.subscriptions[variable.index()].push((propagator, event));
+        }
+        self.subscribed[propagator] = subscriptions.to_vec();
+    }
+
+    /// Wakes up the propagators subscribed to `event` on the variable, but `except`.
+    pub fn notify(&mut self, variable: VarId, event: Event, except: Option<usize>) {
+        if let Some(subscriptions) = self.subscriptions.get(variable.index()) {
+            for &(propagator, subscribed) in subscriptions {
+                if event <= subscribed && Some(propagator) != except && !self.queued[propagator] {
+                    self.queued[propagator] = true;
+                    self.queues[self.priorities[propagator] as usize].push_back(propagator);
+                }
+            }
+        }
+    }
+
+    /// The next propagator to run, from the cheapest class.
+    pub fn pop(&mut self) -> Option<usize> {
+        let propagator = self.queues.iter_mut().find_map(|queue| queue.pop_front())?;
+        self.queued[propagator] = false;
+        Some(propagator)
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, Priority, PropagationQueue};
+    use crate::variable::VarId;
+
+    #[test]
+    fn only_subscribed_propagators_wake_up_cheapest_first() {
+        let (x, y) = (VarId::new(0), VarId::new(1));
+        let mut queue = PropagationQueue::new();
+        let expensive = queue.add_propagator(Priority::Expensive, &[(x, Event::Removed), (y, Event::Removed)]);
+        let bounds = queue.add_propagator(Priority::Cheap, &[(x, Event::Bounds)]);
+        let assigned = queue.add_propagator(Priority::Cheap, &[(x, Event::Assigned), (y, Event::Assigned)]);
+
+        queue.notify(x, Event::Removed, None);
+        assert_eq!(queue.pop(), Some(expensive));
+        assert_eq!(queue.pop(), None);
+
+        queue.notify(y, Event::Removed, None);
+        queue.notify(x, Event::Bounds, None);
+        queue.notify(x, Event::Bounds, None);
+        assert_eq!(queue.pop(), Some(bounds));
+        assert_eq!(queue.pop(), Some(expensive));
+        assert_eq!(queue.pop(), None);
+
+        queue.notify(x, Event::Assigned, Some(bounds));
+        assert_eq!(queue.pop(), Some(assigned));
+        queue.clear();
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn resubscribed_propagators_follow_their_new_variables() {
+        let (x, y) = (VarId::new(0), VarId::new(1));
+        let mut queue = PropagationQueue::new();
+        let watcher = queue.add_propagator(Priority::Cheap, &[(x, Event::Assigned)]);
+        queue.resubscribe(watcher, &[(y, Event::Assigned)]);
+        assert_eq!(queue.subscriptions(watcher), &[(y, Event::Assigned)]);
+
+        queue.notify(x, Event::Assigned, None);
+        assert_eq!(queue.pop(), None);
+        queue.notify(y, Event::Assigned, None);
+        assert_eq!(queue.pop(), Some(watcher));
+    }
+}
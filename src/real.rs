@@ -0,0 +1,431 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::constraint::{Constraint, ConstraintError};
+use crate::domain::{real_box, Domains};
+use crate::propagation::Event;
+use crate::variable::{Assignments, VarId};
+
+
+// closed range of reals, empty when its bounds cross
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Interval {
+    min: f64,
+    max: f64,
+}
+
+impl Interval {
+    const ALL: Interval = Interval { min: f64::NEG_INFINITY, max: f64::INFINITY };
+
+    fn new(min: f64, max: f64) -> Self {
+        Interval {
+            min,
+            max,
+        }
+    }
+
+    fn point(value: f64) -> Self {
+        Interval::new(value, value)
+    }
+
+    fn is_empty(self) -> bool {
+        self.min > self.max || self.min.is_nan() || self.max.is_nan()
+    }
+
+    fn contains_zero(self) -> bool {
+        self.min <= 0.0 && 0.0 <= self.max
+    }
+
+    fn intersect(self, other: Interval) -> Interval {
+        Interval::new(self.min.max(other.min), self.max.min(other.max))
+    }
+
+    // widened by `ulps` floating point steps on each side, covering the rounding of the bounds
+    fn outward(self, ulps: u32) -> Interval {
+        let (mut min, mut max) = (self.min, self.max);
+        for _ in 0..ulps {
+            min = min.next_down();
+            max = max.next_up();
+        }
+        Interval::new(min, max)
+    }
+
+    fn hull(self, other: Interval) -> Interval {
+        match (self.is_empty(), other.is_empty()) {
+            (true, _) => other,
+            (_, true) => self,
+            _ => Interval::new(self.min.min(other.min), self.max.max(other.max)),
+        }
+    }
+
+    fn add(self, other: Interval) -> Interval {
+        Interval::new(self.min + other.min, self.max + other.max).outward(1)
+    }
+
+    fn sub(self, other: Interval) -> Interval {
+        Interval::new(self.min - other.max, self.max - other.min).outward(1)
+    }
+
+    fn neg(self) -> Interval {
+        Interval::new(-self.max, -self.min)
+    }
+
+    fn mul(self, other: Interval) -> Interval {
+        // 0 * inf is 0 here, the bound being reached by the 0 side
+        let products = [self.min * other.min, self.min * other.max, self.max * other.min, self.max * other.max]
+            .map(|product| if product.is_nan() { 0.0 } else { product });
+        Interval::new(products.iter().cloned().fold(f64::INFINITY, f64::min),
+                      products.iter().cloned().fold(f64::NEG_INFINITY, f64::max)).outward(1)
+    }
+
+    fn div(self, other: Interval) -> Interval {
+        if other.contains_zero() {
+            Interval::ALL
+        } else {
+            self.mul(Interval::new(1.0 / other.max, 1.0 / other.min).outward(1))
+        }
+    }
+
+    fn power(self, exponent: u32) -> Interval {
+        let power = |value: f64| value.powi(exponent as i32);
+        let (low, high) = (self.min.abs().min(self.max.abs()), self.min.abs().max(self.max.abs()));
+        let result = if exponent == 0 {
+            return Interval::point(1.0);
+        } else if exponent % 2 == 1 {
+            Interval::new(power(self.min), power(self.max))
+        } else if self.contains_zero() {
+            Interval::new(0.0, power(high))
+        } else {
+            Interval::new(power(low), power(high))
+        };
+        // each multiplication may round
+        result.outward(exponent)
+    }
+
+    // the reals of `base` whose power falls within the interval
+    fn root(self, exponent: u32, base: Interval) -> Interval {
+        let root = |value: f64| value.signum() * value.abs().powf(1.0 / exponent as f64);
+        if exponent == 0 {
+            base
+        } else if exponent % 2 == 1 {
+            base.intersect(Interval::new(root(self.min), root(self.max)).outward(2))
+        } else if self.max < 0.0 {
+            Interval::new(f64::INFINITY, f64::NEG_INFINITY)
+        } else {
+            let positive = Interval::new(root(self.min.max(0.0)), root(self.max)).outward(2);
+            base.intersect(positive.neg()).hull(base.intersect(positive))
+        }
+    }
+}
+
+
+/// Arithmetic over real variables, integer variables taking part with their values as reals.
+#[derive(Debug, Clone)]
+pub enum RealExpression {
+    Constant(f64),
+    Variable(VarId),
+    Sum(Box<RealExpression>, Box<RealExpression>),
+    Difference(Box<RealExpression>, Box<RealExpression>),
+    Product(Box<RealExpression>, Box<RealExpression>),
+    Quotient(Box<RealExpression>, Box<RealExpression>),
+    Negation(Box<RealExpression>),
+    Power(Box<RealExpression>, u32),
+}
+
+impl From<f64> for RealExpression {
+    fn from(value: f64) -> Self {
+        RealExpression::Constant(value)
+    }
+}
+
+impl From<VarId> for RealExpression {
+    fn from(variable: VarId) -> Self {
+        RealExpression::Variable(variable)
+    }
+}
+
+pub fn pow(expression: impl Into<RealExpression>, exponent: u32) -> RealExpression {
+    RealExpression::Power(Box::new(expression.into()), exponent)
+}
+
+macro_rules! binary_operator {
+    ($operator:ident, $method:ident, $variant:ident) => {
+        impl<T: Into<RealExpression>> $operator<T> for RealExpression {
+            type Output = RealExpression;
+
+            fn $method(self, other: T) -> RealExpression {
+                RealExpression::$variant(Box::new(self), Box::new(other.into()))
+            }
+        }
+
+        impl $operator<RealExpression> for f64 {
+            type Output = RealExpression;
+
+            fn $method(self, other: RealExpression) -> RealExpression {
+                RealExpression::$variant(Box::new(self.into()), Box::new(other))
+            }
+        }
+    };
+}
+
+binary_operator!(Add, add, Sum);
+binary_operator!(Sub, sub, Difference);
+binary_operator!(Mul, mul, Product);
+binary_operator!(Div, div, Quotient);
+
+impl Neg for RealExpression {
+    type Output = RealExpression;
+
+    fn neg(self) -> RealExpression {
+        RealExpression::Negation(Box::new(self))
+    }
+}
+
+impl RealExpression {
+    pub fn less_equal(self, other: impl Into<RealExpression>) -> (RealConstraint, Vec<VarId>) {
+        RealConstraint::new(self - other, Interval::new(f64::NEG_INFINITY, 0.0))
+    }
+
+    pub fn greater_equal(self, other: impl Into<RealExpression>) -> (RealConstraint, Vec<VarId>) {
+        RealConstraint::new(self - other, Interval::new(0.0, f64::INFINITY))
+    }
+
+    pub fn equal(self, other: impl Into<RealExpression>) -> (RealConstraint, Vec<VarId>) {
+        RealConstraint::new(self - other, Interval::point(0.0))
+    }
+
+    fn collect_variables(&self, variables: &mut Vec<VarId>) {
+        match self {
+            RealExpression::Constant(_) => {}
+            RealExpression::Variable(variable) => {
+                if !variables.contains(variable) {
+                    variables.push(*variable);
+                }
+            }
+            RealExpression::Sum(left, right) | RealExpression::Difference(left, right)
+            | RealExpression::Product(left, right) | RealExpression::Quotient(left, right) => {
+                left.collect_variables(variables);
+                right.collect_variables(variables);
+            }
+            RealExpression::Negation(expression) | RealExpression::Power(expression, _) => expression.collect_variables(variables),
+        }
+    }
+
+    fn evaluate(&self, bounds: &[(VarId, Interval)]) -> Interval {
+        match self {
+            RealExpression::Constant(value) => Interval::point(*value),
+            RealExpression::Variable(variable) => bounds.iter()
+                .find(|(other, _)| other == variable)
+                .map_or(Interval::ALL, |&(_, interval)| interval),
+            RealExpression::Sum(left, right) => left.evaluate(bounds).add(right.evaluate(bounds)),
+            RealExpression::Difference(left, right) => left.evaluate(bounds).sub(right.evaluate(bounds)),
+            RealExpression::Product(left, right) => left.evaluate(bounds).mul(right.evaluate(bounds)),
+            RealExpression::Quotient(left, right) => left.evaluate(bounds).div(right.evaluate(bounds)),
+            RealExpression::Negation(expression) => expression.evaluate(bounds).neg(),
+            RealExpression::Power(expression, exponent) => expression.evaluate(bounds).power(*exponent),
+        }
+    }
+
+    // the backward pass of HC4-revise, narrows the variables' bounds so that the expression can
+    // fall within `target`, false when it cannot. The operands are evaluated again on the way
+    // down, which is cheap for the small expressions this is meant for.
+    fn narrow(&self, target: Interval, bounds: &mut [(VarId, Interval)]) -> bool {
+        let target = target.intersect(self.evaluate(bounds));
+        if target.is_empty() {
+            return false;
+        }
+
+        match self {
+            RealExpression::Constant(_) => true,
+            RealExpression::Variable(variable) => {
+                if let Some((_, interval)) = bounds.iter_mut().find(|(other, _)| other == variable) {
+                    *interval = target;
+                }
+                true
+            }
+            RealExpression::Sum(left, right) => {
+                left.narrow(target.sub(right.evaluate(bounds)), bounds)
+                    && right.narrow(target.sub(left.evaluate(bounds)), bounds)
+            }
+            RealExpression::Difference(left, right) => {
+                left.narrow(target.add(right.evaluate(bounds)), bounds)
+                    && right.narrow(left.evaluate(bounds).sub(target), bounds)
+            }
+            RealExpression::Product(left, right) => {
+                left.narrow(target.div(right.evaluate(bounds)), bounds)
+                    && right.narrow(target.div(left.evaluate(bounds)), bounds)
+            }
+            RealExpression::Quotient(left, right) => {
+                left.narrow(target.mul(right.evaluate(bounds)), bounds)
+                    && right.narrow(left.evaluate(bounds).div(target), bounds)
+            }
+            RealExpression::Negation(expression) => expression.narrow(target.neg(), bounds),
+            RealExpression::Power(expression, exponent) => {
+                let base = target.root(*exponent, expression.evaluate(bounds));
+                expression.narrow(base, bounds)
+            }
+        }
+    }
+}
+
+
+/// Comparison of a real expression with 0, made by `RealExpression::less_equal`, `greater_equal`
+/// and `equal` along with the variables it must be called with.
+///
+/// Propagates by HC4-revise: the expression is evaluated over the bounds of the variables, then
+/// the bounds are narrowed from the root of the expression down to its variables. An assigned real
+/// variable stands for its whole box, so that a solution is a box which may hold a solution rather
+/// than one that surely does.
+#[derive(Debug, Clone)]
+pub struct RealConstraint {
+    expression: RealExpression,
+    target: Interval,
+}
+
+impl RealConstraint {
+    fn new(expression: RealExpression, target: Interval) -> (Self, Vec<VarId>) {
+        let mut variables: Vec<VarId> = Vec::default();
+        expression.collect_variables(&mut variables);
+        (RealConstraint { expression, target }, variables)
+    }
+}
+
+impl Constraint for RealConstraint {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        let mut bounds: Vec<(VarId, Interval)> = Vec::with_capacity(variables.len());
+        for &variable in variables {
+            let domain = match domains.get(&variable) {
+                Some(domain) => domain,
+                None => return Ok(false),
+            };
+            let interval = match (assignments.get(&variable), domain.precision()) {
+                (Some(&value), Some(precision)) => {
+                    let (min, max) = real_box(value, precision);
+                    Interval::new(min, max)
+                }
+                (Some(&value), None) => Interval::point(value as f64),
+                (None, _) => match domain.real_bounds() {
+                    Some((min, max)) => Interval::new(min, max),
+                    None => return Ok(false),
+                },
+            };
+            bounds.push((variable, interval));
+        }
+
+        let before = bounds.clone();
+        if !self.expression.narrow(self.target, &mut bounds) {
+            return Ok(false);
+        }
+        if !forward_check {
+            return Ok(true);
+        }
+
+        for (&(variable, interval), &(_, old)) in bounds.iter().zip(before.iter()) {
+            if interval == old || assignments.contains_key(&variable) {
+                continue;
+            }
+            let domain = domains.get_mut(&variable).unwrap();
+            domain.set_real_bounds(interval.min, interval.max);
+            if domain.is_empty() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // narrows the bounds once rather than trying each value of the variables, which may be many
+    fn pre_process(&self,
+                   variables: &[VarId],
+                   domains: &mut Domains) -> bool
+    {
+        let _ = self.call(variables, domains, &mut Assignments::new(), true);
+        false
+    }
+
+    fn subscriptions(&self, variables: &[VarId]) -> Vec<(VarId, Event)> {
+        variables.iter().map(|&variable| (variable, Event::Bounds)).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{pow, Interval, RealExpression};
+    use crate::constraint::Constraint;
+    use crate::domain::{real_box, Domain, Domains};
+    use crate::problem::Problem;
+    use crate::variable::{Assignments, VarId};
+
+    // the interval covers `[min, max]`, give or take a rounding
+    fn covers(interval: Interval, min: f64, max: f64) -> bool {
+        interval.min <= min && max <= interval.max && min - interval.min < 1e-12 && interval.max - max < 1e-12
+    }
+
+    #[test]
+    fn interval_operations_bound_every_result() {
+        let (x, y) = (Interval::new(-1.0, 2.0), Interval::new(3.0, 4.0));
+        assert!(covers(x.mul(y), -4.0, 8.0));
+        assert_eq!(y.div(x), Interval::ALL);
+        assert!(covers(x.div(y), -1.0 / 3.0, 2.0 / 3.0));
+        assert!(covers(x.power(2), 0.0, 4.0));
+        assert!(covers(x.power(3), -1.0, 8.0));
+        assert!(covers(Interval::new(1.0, 4.0).root(2, Interval::new(-3.0, 1.5)), -2.0, 1.5));
+        assert!(Interval::new(-2.0, -1.0).root(2, Interval::ALL).is_empty());
+        assert!(covers(Interval::point(0.0).mul(Interval::ALL), 0.0, 0.0));
+        // 0.1 + 0.2 rounds above 0.3
+        assert!(covers(Interval::point(0.1).add(Interval::point(0.2)).sub(Interval::point(0.3)), 0.0, 0.0));
+    }
+
+    #[test]
+    fn hc4_narrows_through_the_expression() {
+        // x + 2 * y == 10.2, y is at least 3.5 so x is at most 3.2 and y at most 5.1
+        let (x, y) = (VarId::new(0), VarId::new(1));
+        let mut domains = Domains::new(vec![Domain::real(0.0, 10.0, 0.5), Domain::real(3.5, 20.0, 0.5)]);
+        let (constraint, variables) = (RealExpression::from(x) + 2.0 * RealExpression::from(y)).equal(10.2);
+        assert_eq!(variables, vec![x, y]);
+        assert!(constraint.call(&variables, &mut domains, &mut Assignments::new(), true).unwrap());
+        assert_eq!(domains.get(&x).unwrap().real_bounds(), Some((0.0, 3.5)));
+        assert_eq!(domains.get(&y).unwrap().real_bounds(), Some((3.5, 5.5)));
+
+        let (constraint, variables) = pow(x, 2).less_equal(-1.0);
+        assert!(!constraint.call(&variables, &mut domains, &mut Assignments::new(), true).unwrap());
+    }
+
+    #[test]
+    fn bisection_closes_in_on_the_roots() {
+        let precision = 1e-4;
+        let mut problem = Problem::new();
+        let x = problem.add_real_variable("x", -2.0, 2.0, precision);
+        let (constraint, variables) = pow(x, 2).equal(2.0);
+        problem.add_constraint(constraint, &variables);
+
+        let boxes: Vec<(f64, f64)> = problem.get_solutions().unwrap().iter()
+            .map(|solution| real_box(solution[&x], precision))
+            .collect();
+        for &root in [-2f64.sqrt(), 2f64.sqrt()].iter() {
+            assert!(boxes.iter().any(|&(min, max)| min <= root && root <= max));
+        }
+        assert!(boxes.iter().all(|&(min, max)| (min.abs() - 2f64.sqrt()).abs() <= precision && max - min <= precision * 1.5));
+    }
+
+    #[test]
+    fn integer_variables_take_part_as_reals() {
+        // 3 * x == n with x at most 1.5 leaves n within 0..=4
+        let mut problem = Problem::new();
+        let x = problem.add_real_variable("x", 0.0, 10.0, 0.01);
+        let n = problem.add_variable("n", &(0..=10).collect::<Vec<i32>>());
+        let (constraint, variables) = (3.0 * RealExpression::from(x)).equal(n);
+        problem.add_constraint(constraint, &variables);
+        let (constraint, variables) = RealExpression::from(x).less_equal(1.5);
+        problem.add_constraint(constraint, &variables);
+
+        let mut counts: Vec<i32> = problem.get_solutions().unwrap().iter().map(|solution| solution[&n]).collect();
+        counts.sort_unstable();
+        counts.dedup();
+        assert_eq!(counts, vec![0, 1, 2, 3, 4]);
+    }
+}
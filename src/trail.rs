@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+
+use crate::variable::VarId;
+
+
+/// Handle on a reversible integer of a `Trail`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TrailInt(usize);
+
+/// Handle on a reversible set of a `Trail`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TrailSet(usize);
+
+// how to undo a change
+#[derive(Debug, Clone, Copy)]
+enum Entry {
+    Domain(VarId, usize),
+    Int(TrailInt, i64),
+    Inserted(TrailSet, i32),
+    Removed(TrailSet, i32),
+}
+
+
+/// Search state brought back to a checkpoint by undoing the changes made since, for the domains
+/// of a `Domains` and for the integers and sets registered by propagators.
+///
+/// Changes made while no checkpoint is saved are kept for good.
+#[derive(Debug, Clone, Default)]
+pub struct Trail {
+    entries: Vec<Entry>,
+    // the length of `entries` at each checkpoint, with a number telling the checkpoint apart
+    checkpoints: Vec<(usize, usize)>,
+    ints: Vec<i64>,
+    sets: Vec<HashSet<i32>>,
+    // the checkpoint each domain was last saved in, a domain is saved once per checkpoint
+    saved: Vec<usize>,
+    count: usize,
+}
+
+impl Trail {
+    pub fn new() -> Self {
+        Trail::default()
+    }
+
+    /// Number of checkpoints saved and not restored yet.
+    pub fn level(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    pub fn checkpoint(&mut self) {
+        self.count += 1;
+        self.checkpoints.push((self.entries.len(), self.count));
+    }
+
+    /// Undoes the changes made since the last checkpoint, `restore_domain` brings the domain of
+    /// the variable back to the mark it had when first changed.
+    pub fn restore(&mut self, mut restore_domain: impl FnMut(VarId, usize)) {
+        let bound = match self.checkpoints.pop() {
+            Some((bound, _)) => bound,
+            None => return,
+        };
+
+        for entry in self.entries.drain(bound..).rev() {
+            match entry {
+                Entry::Domain(variable, mark) => restore_domain(variable, mark),
+                Entry::Int(int, value) => self.ints[int.0] = value,
+                Entry::Inserted(set, value) => {
+                    self.sets[set.0].remove(&value);
+                }
+                Entry::Removed(set, value) => {
+                    self.sets[set.0].insert(value);
+                }
+            }
+        }
+    }
+
+    // records the mark of the variable's domain the first time it may change since the last checkpoint
+    pub(crate) fn save_domain(&mut self, variable: VarId, mark: usize) {
+        let checkpoint = match self.checkpoints.last() {
+            Some(&(_, checkpoint)) => checkpoint,
+            None => return,
+        };
+
+        if self.saved.len() <= variable.index() {
+            self.saved.resize(variable.index() + 1, 0);
+        }
+        if self.saved[variable.index()] != checkpoint {
+            self.saved[variable.index()] = checkpoint;
+            self.entries.push(Entry::Domain(variable, mark));
+        }
+    }
+
+    pub fn new_int(&mut self, value: i64) -> TrailInt {
+        self.ints.push(value);
+        TrailInt(self.ints.len() - 1)
+    }
+
+    pub fn int(&self, int: TrailInt) -> i64 {
+        self.ints[int.0]
+    }
+
+    pub fn set_int(&mut self, int: TrailInt, value: i64) {
+        let old = std::mem::replace(&mut self.ints[int.0], value);
+        if !self.checkpoints.is_empty() && old != value {
+            self.entries.push(Entry::Int(int, old));
+        }
+    }
+
+    pub fn new_set(&mut self) -> TrailSet {
+        self.sets.push(HashSet::default());
+        TrailSet(self.sets.len() - 1)
+    }
+
+    pub fn set(&self, set: TrailSet) -> &HashSet<i32> {
+        &self.sets[set.0]
+    }
+
+    pub fn insert(&mut self, set: TrailSet, value: i32) -> bool {
+        let inserted = self.sets[set.0].insert(value);
+        if inserted && !self.checkpoints.is_empty() {
+            self.entries.push(Entry::Inserted(set, value));
+        }
+        inserted
+    }
+
+    pub fn remove(&mut self, set: TrailSet, value: i32) -> bool {
+        let removed = self.sets[set.0].remove(&value);
+        if removed && !self.checkpoints.is_empty() {
+            self.entries.push(Entry::Removed(set, value));
+        }
+        removed
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Trail;
+    use crate::variable::VarId;
+
+    #[test]
+    fn restore_undoes_the_changes_since_the_checkpoint() {
+        let mut trail = Trail::new();
+        let count = trail.new_int(0);
+        let seen = trail.new_set();
+        trail.set_int(count, 1);
+        trail.insert(seen, 7);
+
+        trail.checkpoint();
+        trail.set_int(count, 2);
+        trail.insert(seen, 8);
+        trail.checkpoint();
+        trail.set_int(count, 3);
+        trail.set_int(count, 4);
+        trail.remove(seen, 7);
+        assert_eq!((trail.level(), trail.int(count)), (2, 4));
+
+        trail.restore(|_, _| unreachable!());
+        assert_eq!(trail.int(count), 2);
+        assert_eq!(trail.set(seen).len(), 2);
+        trail.restore(|_, _| unreachable!());
+        assert_eq!((trail.level(), trail.int(count)), (0, 1));
+        assert!(trail.set(seen).contains(&7) && !trail.set(seen).contains(&8));
+    }
+
+    #[test]
+    fn domains_are_saved_once_per_checkpoint() {
+        let (x, y) = (VarId::new(0), VarId::new(1));
+        let mut trail = Trail::new();
+        trail.save_domain(x, 10);
+
+        trail.checkpoint();
+        trail.save_domain(x, 5);
+        trail.save_domain(x, 4);
+        trail.checkpoint();
+        trail.save_domain(x, 3);
+        trail.save_domain(y, 9);
+
+        let mut restored = Vec::default();
+        trail.restore(|variable, mark| restored.push((variable, mark)));
+        assert_eq!(restored, vec![(y, 9), (x, 3)]);
+        // y was only saved in the checkpoint just restored
+        trail.save_domain(y, 8);
+        restored.clear();
+        trail.restore(|variable, mark| restored.push((variable, mark)));
+        assert_eq!(restored, vec![(y, 8), (x, 5)]);
+    }
+}
@@ -1,9 +1,11 @@
+use std::borrow::Cow;
 use std::fmt::{self, Display, Formatter};
 use std::hash::{Hash, Hasher};
+use std::ops::Index;
 
 #[derive(Debug, Eq)]
 pub struct Variable {
-    name: &'static str
+    name: Cow<'static, str>
 }
 
 impl PartialEq for Variable {
@@ -25,15 +27,94 @@ impl Display for Variable {
 }
 
 impl Variable {
-    pub fn new(name: &'static str) -> Self {
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
         Variable {
-            name
+            name: name.into()
         }
     }
 
-    pub const fn unassigned() -> Self {
-        Variable {
-            name: "UNASSIGNED"
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+
+/// Handle on a variable of a `Problem`, the index of the variable in the problem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VarId(usize);
+
+impl VarId {
+    pub fn new(index: usize) -> Self {
+        VarId(index)
+    }
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+
+/// Handles on the variables of an interval of time, `start + duration == end` when the interval
+/// is present. Made by `Problem::add_interval_variable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IntervalVar {
+    pub start: VarId,
+    pub duration: VarId,
+    pub end: VarId,
+    pub presence: VarId,
+}
+
+impl IntervalVar {
+    /// The `[start, duration, end, presence]` variables of each interval, in order, as taken by
+    /// the scheduling constraints.
+    pub fn variables(intervals: &[IntervalVar]) -> Vec<VarId> {
+        intervals.iter().flat_map(|interval| vec![interval.start, interval.duration, interval.end, interval.presence]).collect()
+    }
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct Assignments {
+    values: Vec<Option<i32>>,
+}
+
+impl Assignments {
+    pub fn new() -> Self {
+        Assignments::default()
+    }
+
+    pub fn contains_key(&self, variable: &VarId) -> bool {
+        self.get(variable).is_some()
+    }
+
+    pub fn get(&self, variable: &VarId) -> Option<&i32> {
+        self.values.get(variable.index()).and_then(|value| value.as_ref())
+    }
+
+    pub fn insert(&mut self, variable: VarId, value: i32) -> Option<i32> {
+        if self.values.len() <= variable.index() {
+            self.values.resize(variable.index() + 1, None);
         }
+        self.values[variable.index()].replace(value)
+    }
+
+    pub fn remove(&mut self, variable: &VarId) -> Option<i32> {
+        self.values.get_mut(variable.index()).and_then(|value| value.take())
     }
-}
\ No newline at end of file
+
+    pub fn len(&self) -> usize {
+        self.values.iter().filter(|value| value.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Index<&VarId> for Assignments {
+    type Output = i32;
+
+    fn index(&self, variable: &VarId) -> &i32 {
+        self.get(variable).expect("Variable is not assigned.")
+    }
+}
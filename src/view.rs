@@ -0,0 +1,498 @@
+use std::convert::TryFrom;
+
+use crate::constraint::{ceil_div, floor_div, Constraint, ConstraintError, Relation};
+use crate::domain::{Domain, Domains};
+use crate::expression::{Comparison, Expression};
+use crate::propagation::{Event, Priority};
+use crate::variable::{Assignments, VarId};
+
+
+/// A value derived from a variable, usable by constraints in place of a variable whose domain is
+/// derived from the variable's one. Added to a problem by `Problem::add_view`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    /// `scale * variable + offset`, the scale is not 0.
+    Affine { variable: VarId, scale: i32, offset: i32 },
+    /// 1 when the variable equals `value`, 0 otherwise.
+    Equal { variable: VarId, value: i32 },
+}
+
+impl View {
+    pub fn equal(variable: VarId, value: i32) -> Self {
+        View::Equal { variable, value }
+    }
+
+    /// The variable the view is derived from.
+    pub fn variable(&self) -> VarId {
+        match *self {
+            View::Affine { variable, .. } | View::Equal { variable, .. } => variable,
+        }
+    }
+
+    /// The value of the view when its variable takes `value`.
+    pub fn image(&self, value: i32) -> i32 {
+        match *self {
+            View::Affine { scale, offset, .. } => {
+                let image = scale as i64 * value as i64 + offset as i64;
+                image.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+            }
+            View::Equal { value: other, .. } => (value == other) as i32,
+        }
+    }
+
+    // the values of the view over those of `base`, only the bounds of an interval are mapped so
+    // that its holes and the values between the multiples of the scale are left in
+    fn domain(&self, base: &Domain) -> Domain {
+        match *self {
+            View::Affine { .. } if base.is_interval() => match (base.min(), base.max()) {
+                (Some(min), Some(max)) => {
+                    let (first, last) = (self.image(min), self.image(max));
+                    Domain::interval(first.min(last), first.max(last))
+                }
+                _ => Domain::new(&[]),
+            },
+            View::Affine { .. } => Domain::new(&base.iter().map(|value| self.image(value)).collect::<Vec<i32>>()),
+            View::Equal { value, .. } => {
+                let contains = base.contains(value);
+                let others = base.len() > contains as usize;
+                let values: Vec<i32> = [(others, 0), (contains, 1)].iter().filter(|&&(kept, _)| kept).map(|&(_, image)| image).collect();
+                Domain::new(&values)
+            }
+        }
+    }
+
+    // hides the values of `view` which are no longer the image of a value of `base`, only the
+    // bounds of an interval are followed, images clamped to the range of an i32 are left in
+    fn follow(&self, view: &mut Domain, base: &Domain) {
+        match *self {
+            View::Affine { .. } if base.is_interval() => match (base.min(), base.max()) {
+                (Some(min), Some(max)) => {
+                    let (first, last) = (self.image(min), self.image(max));
+                    view.set_min(first.min(last));
+                    view.set_max(first.max(last));
+                }
+                _ => {
+                    view.set_max(i32::MIN);
+                    view.set_min(i32::MAX);
+                }
+            },
+            View::Affine { scale, offset, .. } => {
+                if view.len() == base.len() {
+                    return;
+                }
+                let image = |value: i32| {
+                    let shifted = value as i64 - offset as i64;
+                    value == i32::MIN || value == i32::MAX
+                        || (shifted % scale as i64 == 0 && i32::try_from(shifted / scale as i64).is_ok_and(|value| base.contains(value)))
+                };
+                let hidden: Vec<i32> = view.iter().filter(|&value| !image(value)).collect();
+                for value in hidden {
+                    view.hide_value(value);
+                }
+            }
+            View::Equal { value, .. } => {
+                if !base.contains(value) && view.contains(1) {
+                    view.hide_value(1);
+                }
+                if base.len() == 1 && base.contains(value) && view.contains(0) {
+                    view.hide_value(0);
+                }
+            }
+        }
+    }
+
+    // hides the values of `base` whose image is no longer in `view`, false when none is left
+    fn narrow(&self, view: &Domain, base: &mut Domain) -> bool {
+        match *self {
+            View::Affine { scale, offset, .. } if base.is_interval() => {
+                let (min, max) = match (view.min(), view.max()) {
+                    (Some(min), Some(max)) => (min as i64 - offset as i64, max as i64 - offset as i64),
+                    _ => return false,
+                };
+                let (low, high) = if scale > 0 { (min, max) } else { (max, min) };
+                let (low, high) = (ceil_div(low, scale as i64), floor_div(high, scale as i64));
+                base.set_min(low.clamp(i32::MIN as i64, i32::MAX as i64) as i32);
+                base.set_max(high.clamp(i32::MIN as i64, i32::MAX as i64) as i32);
+            }
+            View::Affine { .. } => {
+                let hidden: Vec<i32> = base.iter().filter(|&value| !view.contains(self.image(value))).collect();
+                for value in hidden {
+                    base.hide_value(value);
+                }
+            }
+            View::Equal { value, .. } => {
+                if !view.contains(1) && base.contains(value) {
+                    base.hide_value(value);
+                }
+                if !view.contains(0) {
+                    base.set_min(value);
+                    base.set_max(value);
+                }
+            }
+        }
+        !base.is_empty()
+    }
+}
+
+/// `scale * variable + offset` out of an expression linear in a single variable, such as `x + 3`,
+/// `2 * x` or `-x`.
+impl TryFrom<Expression> for View {
+    type Error = ConstraintError;
+
+    fn try_from(expression: Expression) -> Result<Self, Self::Error> {
+        let (variable, scale, offset) = single_variable(&expression)
+            .ok_or_else(|| ConstraintError::new("View must be linear in a single variable."))?;
+        match (i32::try_from(scale), i32::try_from(offset)) {
+            (Ok(scale), Ok(offset)) => Ok(View::Affine { variable, scale, offset }),
+            _ => Err(ConstraintError::new("View coefficients must fit in an i32.")),
+        }
+    }
+}
+
+/// Whether a variable equals a value, out of a comparison such as `x == 5`.
+impl TryFrom<Comparison> for View {
+    type Error = ConstraintError;
+
+    fn try_from(comparison: Comparison) -> Result<Self, Self::Error> {
+        if comparison.relation != Relation::Equal || comparison.offset != 0 {
+            return Err(ConstraintError::new("View must compare a variable for equality."));
+        }
+        // scale * variable + offset == 0
+        let (variable, scale, offset) = single_variable(&(comparison.left - comparison.right))
+            .ok_or_else(|| ConstraintError::new("View must compare a single variable with a constant."))?;
+        if offset % scale != 0 {
+            return Err(ConstraintError::new("View must compare a variable with a value it can take."));
+        }
+        offset.checked_neg()
+            .and_then(|offset| i32::try_from(offset / scale).ok())
+            .map(|value| View::Equal { variable, value })
+            .ok_or_else(|| ConstraintError::new("View must compare a variable with a value it can take."))
+    }
+}
+
+// `(variable, scale, offset)` when the expression is `scale * variable + offset` with a nonzero scale
+fn single_variable(expression: &Expression) -> Option<(VarId, i64, i64)> {
+    let (terms, offset) = expression.linear()?;
+    let variable = terms.first()?.0;
+    if terms.iter().any(|&(other, _)| other != variable) {
+        return None;
+    }
+    let scale = terms.iter().try_fold(0i64, |scale, &(_, coefficient)| scale.checked_add(coefficient))?;
+    if scale == 0 { None } else { Some((variable, scale, offset)) }
+}
+
+
+/// Calls `constraint` with views in place of some of its variables, the variables given being
+/// the ones the views are derived from.
+///
+/// A domain is kept for each view, from the first call or pre-processing on, and shared by the
+/// constraints taking the view. Each call only hides from it the values its variable lost since,
+/// or moves its bounds when the variable is an interval, and the values the constraint hides
+/// from the view are hidden from the variable afterwards. Both go through the trail like any
+/// other change. Made by `Problem::add_constraint` when it is given views.
+#[derive(Debug)]
+pub struct ViewConstraint<C: Constraint> {
+    constraint: C,
+    views: Vec<Option<View>>,
+}
+
+impl<C: Constraint> ViewConstraint<C> {
+    pub fn new(constraint: C, views: Vec<Option<View>>) -> Self {
+        ViewConstraint {
+            constraint,
+            views,
+        }
+    }
+}
+
+impl<C: Constraint> ViewConstraint<C> {
+    // brings the domain of each view up to date and, when its variable is assigned, gives it a
+    // value, returns the handles to call the constraint with along with the views opened
+    fn open(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments) -> (Vec<VarId>, Vec<Opened>)
+    {
+        let mut handles: Vec<VarId> = Vec::with_capacity(variables.len());
+        let mut opened: Vec<Opened> = Vec::default();
+        for (&variable, view) in variables.iter().zip(self.views.iter()) {
+            let view = match view {
+                Some(view) => *view,
+                None => {
+                    handles.push(variable);
+                    continue;
+                }
+            };
+            let handle = match domains.view(&view) {
+                Some(handle) => handle,
+                None => {
+                    let domain = match domains.get(&variable) {
+                        Some(base) => view.domain(base),
+                        None => Domain::new(&[]),
+                    };
+                    domains.add_view(view, domain)
+                }
+            };
+            if let Some((domain, base)) = domains.view_mut(&handle) {
+                view.follow(domain, base);
+            }
+            if let Some(&value) = assignments.get(&variable) {
+                assignments.insert(handle, view.image(value));
+            }
+            handles.push(handle);
+            opened.push(Opened { variable, view, handle, len: domains.get(&handle).unwrap().len() });
+        }
+        (handles, opened)
+    }
+
+    // takes the views' values back out of the assignments, hiding what their domains lost from
+    // their variables when `narrow`. False when a variable is left without values.
+    fn close(opened: Vec<Opened>, domains: &mut Domains, assignments: &mut Assignments, narrow: bool) -> bool {
+        let mut consistent = true;
+        for Opened { variable, view, handle, len } in opened.into_iter().rev() {
+            assignments.remove(&handle);
+            if !narrow || !consistent || assignments.contains_key(&variable) || domains.get(&handle).unwrap().len() == len {
+                continue;
+            }
+            consistent = domains.variable_mut(&handle).is_some_and(|(domain, base)| view.narrow(domain, base));
+        }
+        consistent
+    }
+}
+
+// a view and its domain for the length of a call
+struct Opened {
+    variable: VarId,
+    view: View,
+    handle: VarId,
+    len: usize,
+}
+
+impl<C: Constraint> Constraint for ViewConstraint<C> {
+    fn call(&self,
+            variables: &[VarId],
+            domains: &mut Domains,
+            assignments: &mut Assignments,
+            forward_check: bool) -> Result<bool, ConstraintError>
+    {
+        if variables.len() != self.views.len() {
+            return Err(ConstraintError::new("View constraint needs a variable per view or variable."));
+        }
+
+        let (handles, opened) = self.open(variables, domains, assignments);
+        let result = self.constraint.call(&handles, domains, assignments, forward_check);
+        let narrow = matches!(result, Ok(true));
+        if ViewConstraint::<C>::close(opened, domains, assignments, narrow) { result } else { Ok(false) }
+    }
+
+    // the views' values are dropped for good along with those of their variables, the constraint
+    // is only enforced by the domains if the views map every value back
+    fn pre_process(&self,
+                   variables: &[VarId],
+                   domains: &mut Domains) -> bool
+    {
+        if variables.len() != self.views.len() {
+            return false;
+        }
+
+        let exact = variables.iter().zip(self.views.iter()).all(|(variable, view)| match view {
+            Some(View::Affine { .. }) => !domains.get(variable).is_some_and(|base| base.is_interval()),
+            _ => true,
+        });
+        let mut assignments = Assignments::new();
+        let (handles, opened) = self.open(variables, domains, &mut assignments);
+        let enforced = self.constraint.pre_process(&handles, domains);
+        ViewConstraint::<C>::close(opened, domains, &mut assignments, true);
+        enforced && exact
+    }
+
+    fn subscriptions(&self, variables: &[VarId]) -> Vec<(VarId, Event)> {
+        self.compared(variables, self.constraint.subscriptions(variables))
+    }
+
+    // the constraint resubscribes over the views, its subscriptions are taken back to the variables
+    fn resubscribe(&self,
+                   variables: &[VarId],
+                   subscriptions: &[(VarId, Event)],
+                   domains: &mut Domains,
+                   assignments: &mut Assignments) -> Option<Vec<(VarId, Event)>> {
+        if variables.len() != self.views.len() {
+            return None;
+        }
+
+        let (handles, opened) = self.open(variables, domains, assignments);
+        let subscriptions: Vec<(VarId, Event)> = subscriptions.iter()
+            .filter_map(|&(variable, event)| variables.iter().position(|&other| other == variable).map(|i| (handles[i], event)))
+            .collect();
+        let resubscribed = self.constraint.resubscribe(&handles, &subscriptions, domains, assignments);
+        ViewConstraint::<C>::close(opened, domains, assignments, false);
+
+        let resubscribed = resubscribed?.into_iter()
+            .filter_map(|(handle, event)| handles.iter().position(|&other| other == handle).map(|i| (variables[i], event)))
+            .collect();
+        Some(self.compared(variables, resubscribed))
+    }
+
+    fn priority(&self) -> Priority {
+        self.constraint.priority()
+    }
+}
+
+impl<C: Constraint> ViewConstraint<C> {
+    // a variable compared for equality changes its view by losing a single value
+    fn compared(&self, variables: &[VarId], subscriptions: Vec<(VarId, Event)>) -> Vec<(VarId, Event)> {
+        subscriptions.into_iter()
+            .map(|(variable, event)| {
+                let compared = variables.iter().zip(self.views.iter())
+                    .any(|(&other, view)| other == variable && matches!(view, Some(View::Equal { .. })));
+                (variable, if compared { Event::Removed } else { event })
+            })
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::{View, ViewConstraint};
+    use crate::constraint::{Constraint, ExactSumConstraint, LinearConstraint, Relation};
+    use crate::domain::{Domain, Domains};
+    use crate::expression::Expression;
+    use crate::problem::{BacktrackingSolver, Problem};
+    use crate::variable::{Assignments, VarId};
+
+    // the values of x and y in each solution of `build`, with and without forward checking
+    fn solve(build: impl Fn(&mut Problem, VarId, VarId), x: &[i32], y: &[i32]) -> Vec<Vec<i32>> {
+        let mut found: Vec<Vec<Vec<i32>>> = Vec::default();
+        for &forward_check in [true, false].iter() {
+            let mut problem = Problem::with_solver(Box::new(BacktrackingSolver::new(forward_check)));
+            let (x, y) = (problem.add_variable("x", x), problem.add_variable("y", y));
+            build(&mut problem, x, y);
+            let mut solutions: Vec<Vec<i32>> = problem.get_solutions().unwrap().iter()
+                .map(|solution| vec![solution[&x], solution[&y]])
+                .collect();
+            solutions.sort();
+            found.push(solutions);
+        }
+        assert_eq!(found[0], found[1]);
+        found.pop().unwrap()
+    }
+
+    fn brute(x: &[i32], y: &[i32], check: impl Fn(i32, i32) -> bool) -> Vec<Vec<i32>> {
+        x.iter().flat_map(|&x| y.iter().map(move |&y| vec![x, y])).filter(|t| check(t[0], t[1])).collect()
+    }
+
+    #[test]
+    fn views_come_out_of_expressions_and_comparisons() {
+        let (x, y) = (VarId::new(0), VarId::new(1));
+        assert_eq!(View::try_from(-(2 * x) + 3).unwrap(), View::Affine { variable: x, scale: -2, offset: 3 });
+        assert_eq!(View::try_from(x + x - 1).unwrap(), View::Affine { variable: x, scale: 2, offset: -1 });
+        assert!(View::try_from(x * y).is_err());
+        assert!(View::try_from(x - x).is_err());
+
+        assert_eq!(View::try_from(Expression::from(x).equal(5)).unwrap(), View::equal(x, 5));
+        assert_eq!(View::try_from((2 * x + 1).equal(7)).unwrap(), View::equal(x, 3));
+        assert!(View::try_from((2 * x).equal(5)).is_err());
+        assert!(View::try_from(Expression::from(x).less_equal(5)).is_err());
+    }
+
+    #[test]
+    fn constraints_take_views_like_variables() {
+        let values: Vec<i32> = (0..6).collect();
+        let sum = solve(|problem, x, y| {
+            let shifted = problem.add_view("x + 1", View::try_from(x + 1).unwrap()).unwrap();
+            problem.add_constraint(ExactSumConstraint::new(5, None), &[shifted, y]);
+        }, &values, &values);
+        assert_eq!(sum, brute(&values, &values, |x, y| x + 1 + y == 5));
+
+        let negated = solve(|problem, x, y| {
+            let negated = problem.add_view("-x", View::try_from(-x).unwrap()).unwrap();
+            problem.add_constraint(LinearConstraint::new(vec![3, 1], Relation::GreaterEqual, -4), &[negated, y]);
+        }, &values, &values);
+        assert_eq!(negated, brute(&values, &values, |x, y| -3 * x + y >= -4));
+
+        let counted = solve(|problem, x, y| {
+            let views = [problem.add_view("x == 2", View::equal(x, 2)).unwrap(),
+                         problem.add_view("y == 2", View::equal(y, 2)).unwrap()];
+            problem.add_constraint(ExactSumConstraint::new(1, None), &views);
+        }, &values, &values);
+        assert_eq!(counted, brute(&values, &values, |x, y| (x == 2) != (y == 2)));
+    }
+
+    #[test]
+    fn views_over_ranges_move_their_bounds() {
+        let mut problem = Problem::new();
+        let x = problem.add_range_variable("x", -1_000_000, 1_000_000);
+        let y = problem.add_variable("y", &[0, 1, 2, 3]);
+        let scaled = problem.add_view("3x - 2", View::try_from(3 * x - 2).unwrap()).unwrap();
+        problem.add_constraint(LinearConstraint::new(vec![1, 1], Relation::Equal, 10), &[scaled, y]);
+
+        let mut solutions: Vec<(i32, i32, i32)> = problem.get_solutions().unwrap().iter()
+            .map(|solution| (solution[&x], solution[&y], problem.value(scaled, solution).unwrap()))
+            .collect();
+        solutions.sort();
+        assert_eq!(solutions, vec![(3, 3, 7), (4, 0, 10)]);
+        assert_eq!(problem.variable(scaled).name(), "3x - 2");
+        assert!(problem.add_view("-(3x - 2)", View::try_from(-scaled).unwrap()).is_err());
+    }
+
+    #[test]
+    fn a_failed_call_is_undone_by_the_trail() {
+        let (x, y) = (VarId::new(0), VarId::new(1));
+        let mut domains = Domains::new(vec![Domain::interval(0, 3), Domain::new(&[5, 7])]);
+        let doubled = View::try_from(2 * x).unwrap();
+        let constraint = ViewConstraint::new(LinearConstraint::new(vec![1, -1], Relation::Equal, 0), vec![Some(doubled), None]);
+
+        // 2x == y leaves y at 5, which no x maps to
+        domains.checkpoint();
+        assert!(!constraint.call(&[x, y], &mut domains, &mut Assignments::new(), true).unwrap());
+        assert_eq!(domains.len(), 2);
+        assert!(domains.get(&x).unwrap().is_empty());
+
+        domains.restore();
+        assert_eq!(domains.get(&x).unwrap().values(), vec![0, 1, 2, 3]);
+        assert_eq!(domains.get(&y).unwrap().values(), vec![5, 7]);
+        let view = domains.view(&doubled).unwrap();
+        assert_eq!((domains.get(&view).unwrap().min(), domains.get(&view).unwrap().max()), (Some(0), Some(6)));
+    }
+
+    #[test]
+    fn a_view_keeps_its_domain_across_calls() {
+        let (x, y) = (VarId::new(0), VarId::new(1));
+        let mut domains = Domains::new(vec![Domain::new(&[0, 1, 2, 3]), Domain::new(&[0, 1, 2, 3, 4, 5, 6])]);
+        let shifted = View::try_from(x + 3).unwrap();
+        let constraint = ViewConstraint::new(LinearConstraint::new(vec![1, -1], Relation::LessEqual, 0), vec![Some(shifted), None]);
+
+        // x + 3 <= y
+        assert!(constraint.call(&[x, y], &mut domains, &mut Assignments::new(), true).unwrap());
+        let view = domains.view(&shifted).unwrap();
+        assert_eq!(domains.get(&view).unwrap().values(), vec![3, 4, 5, 6]);
+        assert_eq!(domains.get(&y).unwrap().values(), vec![3, 4, 5, 6]);
+
+        // the same domain follows the values x loses and is brought back with them
+        domains.checkpoint();
+        domains.get_mut(&x).unwrap().hide_value(0);
+        assert!(constraint.call(&[x, y], &mut domains, &mut Assignments::new(), true).unwrap());
+        assert_eq!(domains.view(&shifted), Some(view));
+        assert_eq!(domains.get(&view).unwrap().values(), vec![4, 5, 6]);
+        assert_eq!(domains.get(&y).unwrap().values(), vec![4, 5, 6]);
+
+        domains.restore();
+        assert_eq!(domains.get(&view).unwrap().values(), vec![3, 4, 5, 6]);
+        assert_eq!(domains.get(&y).unwrap().values(), vec![3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn constraints_share_the_domain_of_a_view() {
+        let values: Vec<i32> = (0..5).collect();
+        let shared = solve(|problem, x, y| {
+            let shifted = problem.add_view("x + 1", View::try_from(x + 1).unwrap()).unwrap();
+            let flag = problem.add_view("x == 3", View::equal(x, 3)).unwrap();
+            problem.add_constraint(LinearConstraint::new(vec![1, -1], Relation::LessEqual, 0), &[shifted, y]);
+            problem.add_constraint(LinearConstraint::new(vec![1, 1, 1], Relation::NotEqual, 4), &[shifted, y, flag]);
+        }, &values, &values);
+        assert_eq!(shared, brute(&values, &values, |x, y| x < y && x + 1 + y + (x == 3) as i32 != 4));
+    }
+}
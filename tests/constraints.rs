@@ -0,0 +1,277 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+
+use rust_constraint::automaton::Automaton;
+use rust_constraint::constraint::*;
+use rust_constraint::domain::{real_box, set_elements};
+use rust_constraint::problem::{BacktrackingSolver, Problem};
+use rust_constraint::real::RealExpression;
+use rust_constraint::variable::{IntervalVar, VarId};
+use rust_constraint::view::View;
+
+// adds `constraint` over variables taking `values`, with then without forward checking, the
+// problem must have solutions and each of them must pass `check`
+fn check<C: Constraint + 'static>(constraint: impl Fn() -> C, values: &[&[i32]], check: impl Fn(&[i32]) -> bool) {
+    for &forward_check in [true, false].iter() {
+        let mut problem = Problem::with_solver(Box::new(BacktrackingSolver::new(forward_check)));
+        let variables: Vec<VarId> = values.iter().map(|values| problem.add_variable("x", values)).collect();
+        problem.add_constraint(constraint(), &variables);
+
+        let solutions = problem.get_solutions().unwrap();
+        assert!(!solutions.is_empty(), "forward_check: {}", forward_check);
+        for solution in solutions.iter() {
+            let values: Vec<i32> = variables.iter().map(|variable| solution[variable]).collect();
+            assert!(check(&values), "forward_check: {}, {:?}", forward_check, values);
+        }
+    }
+}
+
+// the number of solutions of `constraint` over variables taking `values`, with then without
+// forward checking
+fn solution_counts<C: Constraint + 'static>(constraint: impl Fn() -> C, values: &[&[i32]]) -> Vec<usize> {
+    [true, false].iter()
+        .map(|&forward_check| {
+            let mut problem = Problem::with_solver(Box::new(BacktrackingSolver::new(forward_check)));
+            let variables: Vec<VarId> = values.iter().map(|values| problem.add_variable("x", values)).collect();
+            problem.add_constraint(constraint(), &variables);
+            problem.get_solutions().unwrap().len()
+        })
+        .collect()
+}
+
+fn set(values: &[i32]) -> HashSet<i32> {
+    values.iter().cloned().collect()
+}
+
+#[test]
+fn element() {
+    check(|| ElementConstraint::new(Some(vec![3, 5, 7])), &[&[0, 1, 2], &[5, 7]], |t| [3, 5, 7][t[0] as usize] == t[1]);
+}
+
+#[test]
+fn regular() {
+    // words of 0s and 1s without two 1s in a row
+    let transitions: HashMap<(usize, i32), usize> = vec![((0, 0), 0), ((0, 1), 1), ((1, 0), 0)].into_iter().collect();
+    let automaton = || Automaton::new(0, vec![0, 1].into_iter().collect(), transitions.clone());
+    check(|| RegularConstraint::new(automaton()), &[&[0, 1][..]; 4], |t| t.windows(2).all(|w| w != [1, 1]));
+}
+
+#[test]
+fn scheduling() {
+    check(|| CumulativeConstraint::new(vec![2, 2, 2], vec![1, 1, 1], 2), &[&[0, 1, 2][..]; 3], |t| {
+        (0..4).all(|time| t.iter().filter(|&&start| start <= time && time < start + 2).count() <= 2)
+    });
+    check(|| NoOverlapConstraint::new(vec![2, 1]), &[&[0, 1, 2], &[0, 1, 2]], |t| t[0] + 2 <= t[1] || t[1] < t[0]);
+}
+
+#[test]
+fn circuits() {
+    check(CircuitConstraint::new, &[&[0, 1, 2][..]; 3], |t| t[t[t[0] as usize] as usize] == 0 && t[0] != 0);
+    check(SubcircuitConstraint::new, &[&[0, 1, 2][..]; 3], |t| t.iter().enumerate().all(|(i, &next)| next as usize == i || t[next as usize] != next));
+}
+
+#[test]
+fn lexicographic_orderings() {
+    check(LexLessConstraint::new, &[&[0, 1][..]; 4], |t| t[..2] < t[2..]);
+    check(LexLessEqConstraint::new, &[&[0, 1][..]; 4], |t| t[..2] <= t[2..]);
+    check(|| LexChainConstraint::new(2, true), &[&[0, 1][..]; 6], |t| t[..2] < t[2..4] && t[2..4] < t[4..]);
+}
+
+#[test]
+fn reification_and_connectives() {
+    let less = || LinearConstraint::new(vec![1, -1], Relation::LessEqual, 0);
+    check(|| ReifiedConstraint::new(less()), &[&[0, 1], &[0, 1, 2], &[0, 1, 2]], |t| (t[0] == 1) == (t[1] <= t[2]));
+    check(|| AndConstraint::new(less(), 2, less()), &[&[0, 1, 2][..]; 4], |t| t[0] <= t[1] && t[2] <= t[3]);
+    check(|| OrConstraint::new(less(), 2, less()), &[&[0, 1, 2][..]; 4], |t| t[0] <= t[1] || t[2] <= t[3]);
+    check(|| ImpliesConstraint::new(less(), 2, less()), &[&[0, 1, 2][..]; 4], |t| t[0] > t[1] || t[2] <= t[3]);
+    check(|| NotConstraint::new(less()), &[&[0, 1, 2][..]; 2], |t| t[0] > t[1]);
+}
+
+#[test]
+fn arithmetic() {
+    let values: &[i32] = &[-3, -2, -1, 1, 2, 3];
+    check(ProductConstraint::new, &[values, values, values], |t| t[2] == t[0] * t[1]);
+    check(DivisionConstraint::new, &[values, values, values], |t| t[2] == t[0] / t[1]);
+    check(ModuloConstraint::new, &[values, values, values], |t| t[2] == t[0] % t[1]);
+    check(AbsConstraint::new, &[values, values], |t| t[1] == t[0].abs());
+    check(MinConstraint::new, &[values, values, values], |t| t[2] == t[0].min(t[1]));
+    check(MaxConstraint::new, &[values, values, values], |t| t[2] == t[0].max(t[1]));
+}
+
+#[test]
+fn counting() {
+    check(|| CountConstraint::new(1), &[&[0, 1], &[0, 1], &[0, 1, 2]], |t| t[..2].iter().filter(|&&x| x == 1).count() as i32 == t[2]);
+    check(|| AmongConstraint::new(set(&[1, 2])), &[&[0, 1, 2], &[0, 1, 2], &[0, 1, 2]], |t| {
+        t[..2].iter().filter(|&&x| x != 0).count() as i32 == t[2]
+    });
+    check(NValueConstraint::new, &[&[0, 1], &[0, 1], &[1, 2]], |t| set(&t[..2]).len() as i32 == t[2]);
+}
+
+#[test]
+fn counting_with_an_unreachable_count() {
+    assert_eq!(solution_counts(|| CountConstraint::new(1), &[&[0, 1], &[0, 1], &[3]]), [0, 0]);
+    assert_eq!(solution_counts(|| AmongConstraint::new(set(&[1, 2])), &[&[0, 1, 2], &[0, 1, 2], &[5]]), [0, 0]);
+    assert_eq!(solution_counts(NValueConstraint::new, &[&[0, 1], &[0, 1], &[3]]), [0, 0]);
+}
+
+#[test]
+fn channeling() {
+    check(InverseConstraint::new, &[&[0, 1][..]; 4], |t| t[t[0] as usize + 2] == 0 && t[t[1] as usize + 2] == 1);
+    check(|| ChannelingConstraint::new(1), &[&[0, 1], &[0, 1], &[1, 2]], |t| t[(t[2] - 1) as usize] == 1 && t[0] + t[1] == 1);
+}
+
+#[test]
+fn bin_packing() {
+    check(|| BinPackingConstraint::new(vec![2, 1, 1]), &[&[0, 1], &[0, 1], &[0, 1], &[2], &[2]], |t| {
+        (0..2).all(|bin| (0..3).filter(|&i| t[i] == bin).map(|i| [2, 1, 1][i]).sum::<i32>() == t[3 + bin as usize])
+    });
+}
+
+#[test]
+fn sliding_windows() {
+    check(|| SlidingSumConstraint::new(2, 1, 2), &[&[0, 1, 2][..]; 3], |t| t.windows(2).all(|w| (1..=2).contains(&(w[0] + w[1]))));
+    check(|| SequenceConstraint::new(set(&[1]), 2, 1, 1), &[&[0, 1][..]; 4], |t| t.windows(2).all(|w| w[0] + w[1] == 1));
+}
+
+#[test]
+fn expressions() {
+    let mut problem = Problem::new();
+    let x = problem.add_variable("x", &[0, 1, 2, 3]);
+    let y = problem.add_variable("y", &[0, 1, 2, 3]);
+    let (constraint, variables) = rust_constraint::constraint!(x + 2 * y == 5);
+    problem.add_constraint(constraint, &variables);
+    for solution in problem.get_solutions().unwrap() {
+        assert_eq!(solution[&x] + 2 * solution[&y], 5);
+    }
+}
+
+#[test]
+fn named_function() {
+    let mut problem = Problem::new();
+    let x = problem.add_variable("x", &[1, 2, 3]);
+    let y = problem.add_variable("y", &[1, 2, 3]);
+    problem.add_constraint(NamedFunctionConstraint::new(move |a: &Assignment| Ok(a[x] < a[y]), true), &[x, y]);
+    assert_eq!(problem.get_solutions().unwrap().len(), 3);
+}
+
+
+#[test]
+fn range_variables() {
+    let mut problem = Problem::new();
+    let a = problem.add_range_variable("a", 0, 1_000_000);
+    let b = problem.add_range_variable("b", 0, 1_000_000);
+    problem.add_constraint(CumulativeConstraint::new(vec![400_000, 700_000], vec![1, 1], 1), &[a, b]);
+    problem.add_constraint(LinearConstraint::new(vec![1, 1], Relation::Equal, 1_000_000), &[a, b]);
+    problem.add_constraint(LinearConstraint::new(vec![1, 1], Relation::NotEqual, 999_999), &[a, b]);
+
+    let solution = problem.get_solution().unwrap().unwrap();
+    assert_eq!((solution[&a], solution[&b]), (0, 1_000_000));
+
+    problem.add_constraint(LinearConstraint::new(vec![1], Relation::GreaterEqual, 1), &[a]);
+    let solution = problem.get_solution().unwrap().unwrap();
+    assert_eq!((solution[&a], solution[&b]), (1, 999_999));
+}
+
+#[test]
+fn boolean_variables() {
+    // exactly two of four people, a needs b or c, and d excludes a
+    let mut problem = Problem::new();
+    let people: Vec<VarId> = ["a", "b", "c", "d"].iter().map(|&name| problem.add_bool_variable(name)).collect();
+    problem.add_constraint(BoolOrConstraint::new(vec![false, true, true]), &people[..3]);
+    problem.add_constraint(BoolOrConstraint::new(vec![false, false]), &[people[3], people[0]]);
+    problem.add_constraint(ExactSumConstraint::new(2, None), &people);
+
+    let mut solutions: Vec<Vec<i32>> = problem.get_solutions().unwrap().iter()
+        .map(|solution| people.iter().map(|person| solution[person]).collect())
+        .collect();
+    solutions.sort();
+    assert_eq!(solutions, vec![vec![0, 0, 1, 1], vec![0, 1, 0, 1], vec![0, 1, 1, 0], vec![1, 0, 1, 0], vec![1, 1, 0, 0]]);
+}
+
+#[test]
+fn set_variables() {
+    // two teams of two out of five people, 0 leads the first team and whoever of 1 and 2 is in the
+    // second team must be in the first one, which rules both out of the second team
+    let mut problem = Problem::new();
+    let first = problem.add_set_variable("first", &[0], &[0, 1, 2, 3, 4]).unwrap();
+    let second = problem.add_set_variable("second", &[], &[0, 1, 2, 3, 4]).unwrap();
+    let size = problem.add_variable("size", &[2]);
+    let fell_out = problem.add_set_variable("fell out", &[1, 2], &[1, 2]).unwrap();
+    let overlap = problem.add_set_variable("overlap", &[], &[0, 1, 2, 3, 4]).unwrap();
+    problem.add_constraint(CardinalityConstraint::new(), &[first, size]);
+    problem.add_constraint(CardinalityConstraint::new(), &[second, size]);
+    problem.add_constraint(DisjointConstraint::new(), &[first, second]);
+    problem.add_constraint(IntersectionConstraint::new(), &[second, fell_out, overlap]);
+    problem.add_constraint(SubsetConstraint::new(), &[overlap, first]);
+
+    let solutions = problem.get_solutions().unwrap();
+    let mut teams: Vec<(Vec<i32>, Vec<i32>)> = solutions.iter()
+        .map(|solution| (set_elements(solution[&first]), set_elements(solution[&second])))
+        .collect();
+    teams.sort();
+    assert_eq!(teams, vec![(vec![0, 1], vec![3, 4]), (vec![0, 2], vec![3, 4])]);
+}
+
+#[test]
+fn interval_variables() {
+    // a job of 3 then a job of 2 on a single machine by time 6, the second job running on either of
+    // two tools, the first of which is only free from time 4
+    let mut problem = Problem::new();
+    let cut = problem.add_interval_variable("cut", (0, 6), &[3], false);
+    let drill = problem.add_interval_variable("drill", (0, 6), &[2], false);
+    let tools = [problem.add_interval_variable("old tool", (4, 6), &[2], true),
+                 problem.add_interval_variable("new tool", (0, 6), &[2], true)];
+    problem.add_constraint(EndBeforeStartConstraint::new(0), &IntervalVar::variables(&[cut, drill]));
+    problem.add_constraint(IntervalNoOverlapConstraint::new(), &IntervalVar::variables(&[cut, drill]));
+    problem.add_constraint(AlternativeConstraint::new(), &IntervalVar::variables(&[drill, tools[0], tools[1]]));
+
+    let mut schedules: Vec<Vec<i32>> = problem.get_solutions().unwrap().iter()
+        .map(|solution| vec![solution[&cut.start], solution[&drill.start], solution[&tools[0].presence]])
+        .collect();
+    schedules.sort();
+    assert_eq!(schedules, vec![vec![0, 3, 0], vec![0, 4, 0], vec![0, 4, 1], vec![1, 4, 0], vec![1, 4, 1]]);
+
+    let names: Vec<&str> = IntervalVar::variables(&[tools[0]]).iter().map(|&variable| problem.variable(variable).name()).collect();
+    assert_eq!(names, vec!["old tool.start", "old tool.duration", "old tool.end", "old tool.presence"]);
+}
+
+#[test]
+fn real_variables() {
+    // a bill of 12.5 split over 3 to 5 people, each share below 4.5
+    let precision = 0.01;
+    let mut problem = Problem::new();
+    let share = problem.add_real_variable("share", 0.0, 100.0, precision);
+    let people = problem.add_variable("people", &[1, 2, 3, 4, 5]);
+    let (constraint, variables) = (RealExpression::from(share) * people).equal(12.5);
+    problem.add_constraint(constraint, &variables);
+    let (constraint, variables) = RealExpression::from(share).less_equal(4.5);
+    problem.add_constraint(constraint, &variables);
+
+    let solutions = problem.get_solutions().unwrap();
+    let mut counts: Vec<i32> = solutions.iter().map(|solution| solution[&people]).collect();
+    counts.sort_unstable();
+    counts.dedup();
+    assert_eq!(counts, vec![3, 4, 5]);
+    for solution in solutions.iter() {
+        let (min, max) = real_box(solution[&share], precision);
+        let exact = 12.5 / solution[&people] as f64;
+        assert!(min - precision <= exact && exact <= max + precision, "{} {:?}", exact, (min, max));
+    }
+}
+
+#[test]
+fn views() {
+    // x + 1 and y sum to 4 and differ, without any variable for x + 1
+    let mut problem = Problem::new();
+    let x = problem.add_variable("x", &[0, 1, 2, 3]);
+    let y = problem.add_variable("y", &[0, 1, 2, 3]);
+    let next = problem.add_view("x + 1", View::try_from(x + 1).unwrap()).unwrap();
+    problem.add_constraint(ExactSumConstraint::new(4, None), &[next, y]);
+    problem.add_constraint(AllDifferentConstraint::new(), &[next, y]);
+
+    let mut solutions: Vec<(i32, i32)> = problem.get_solutions().unwrap().iter()
+        .map(|solution| (solution[&x], solution[&y]))
+        .collect();
+    solutions.sort();
+    assert_eq!(solutions, vec![(0, 3), (2, 1), (3, 0)]);
+}